@@ -3,6 +3,7 @@ import init, { WorldWrapper } from "./pkg/wt_client.js";
 let world;
 
 let isMouseDown = false;
+let wasMouseDown = false;
 let mouseX = 0;
 let mouseY = 0;
 
@@ -40,6 +41,7 @@ async function run_game_loop() {
     try {
         world = new WorldWrapper();
         window.world = world;
+        resizeCanvas();
     } catch (err) {
         console.error("Failed to create WorldWrapper:", err);
         return;
@@ -48,12 +50,16 @@ async function run_game_loop() {
     const timer = new Timer(() => {
         try {
             if (isMouseDown) {
-                //console.log("Click Hold");
-                //world.input_click_hold(mouseX, mouseY);
+                send_datagram(world.input_click_hold(mouseX, mouseY));
             }
-            else {
-                //console.log("Click Released");
-                //world.input_click_released();
+            else if (wasMouseDown) {
+                send_datagram(world.input_click_released());
+            }
+            wasMouseDown = isMouseDown;
+
+            const command = world.input_command();
+            if (command.length > 0) {
+                send_datagram(command);
             }
             world.update();
         } catch (err) {
@@ -67,7 +73,16 @@ async function run_game_loop() {
 async function connect() {
 
     try {
-      currentTransport = new WebTransport("https://us.playdodgeball.dev:8443/");
+      // `?room=<id>` on the page picks a room and `?team=<id>` a team; without them the
+      // server uses its default room and balances teams
+      const params = new URLSearchParams(window.location.search);
+      const room = params.get("room") ?? "";
+      const team = params.get("team");
+      let url = `https://us.playdodgeball.dev:8443/${encodeURIComponent(room)}`;
+      if (team !== null) {
+        url += `?team=${encodeURIComponent(team)}`;
+      }
+      currentTransport = new WebTransport(url);
       console.log('Initiating connection...');
     } catch (e) {
       console.log('Failed to create connection object. ' + e, 'error');
@@ -131,48 +146,64 @@ async function acceptUnidirectionalStreams(transport) {
             const number = streamNumber++;
             console.log('📥 Incoming unidirectional stream #' + number);
 
-            // Read binary chunks directly from the stream
-            const binaryReader = stream.getReader();
-            const chunks = [];
+            // Not awaited: a stream stays open for the whole session
+            readFramedMessages(stream, number);
+        }
+    } catch (e) {
+        console.log('Error while accepting streams: ' + e);
+    }
+}
 
-            try {
-                while (true) {
-                    const { value, done } = await binaryReader.read();
-                    if (done) {
-                        console.log('Stream #' + number + ' closed.');
-                        break;
-                    }
 
-                    chunks.push(value);
-                }
 
-                // Combine all chunks into a single Uint8Array
-                const totalLength = chunks.reduce((sum, chunk) => sum + chunk.length, 0);
-                const fullMessage = new Uint8Array(totalLength);
-                let offset = 0;
-                for (const chunk of chunks) {
-                    fullMessage.set(chunk, offset);
-                    offset += chunk.length;
-                }
+// Reliable messages arrive in order on one long-lived stream, each prefixed with its length as a
+// little-endian u32, and are handed to the world as soon as they are complete
+async function readFramedMessages(stream, number) {
+    const binaryReader = stream.getReader();
+    let pending = new Uint8Array(0);
 
-                world.receive_message(fullMessage);
+    try {
+        while (true) {
+            const { value, done } = await binaryReader.read();
+            if (done) {
+                console.log('Stream #' + number + ' closed.');
+                break;
+            }
 
-            } catch (e) {
-                console.log(`Error on stream #${number}: ${e}`);
+            const combined = new Uint8Array(pending.length + value.length);
+            combined.set(pending, 0);
+            combined.set(value, pending.length);
+            pending = combined;
+
+            while (pending.length >= 4) {
+                const length = new DataView(pending.buffer, pending.byteOffset, 4).getUint32(0, true);
+                if (pending.length < 4 + length) {
+                    break;
+                }
+                world.receive_message(pending.subarray(4, 4 + length));
+                pending = pending.subarray(4 + length);
             }
         }
     } catch (e) {
-        console.log('Error while accepting streams: ' + e);
+        console.log(`Error on stream #${number}: ${e}`);
     }
 }
 
-
+async function send_datagram(data) {
+    if (!currentTransportDatagramWriter) {
+        return;
+    }
+    try {
+        await currentTransportDatagramWriter.write(data);
+    } catch (e) {
+        console.log('Error while sending data: ' + e, 'error');
+    }
+}
 
 async function send_input_click_pressed(mouseX, mouseY) {
     try {
         let data = world.input_click_pressed(mouseX, mouseY);
         await currentTransportDatagramWriter.write(data);
-        console.log("Sent Message");
     } catch (e) {
         addToEventLog('Error while sending data: ' + e, 'error');
     }
@@ -183,31 +214,86 @@ run_game_loop();
 
 const canvas = document.getElementById("my_canvas");
 
-canvas.addEventListener("mousedown", async (event) => {
+let activePointerId = null;
+
+function updatePointer(event) {
     const rect = canvas.getBoundingClientRect();
     mouseX = event.clientX - rect.left;
     mouseY = event.clientY - rect.top;
+}
+
+function resizeCanvas() {
+    if (world) {
+        const rect = canvas.getBoundingClientRect();
+        world.resize(rect.width, rect.height, window.devicePixelRatio || 1);
+    }
+}
+
+function releasePointer(event) {
+    if (event.pointerId !== activePointerId) {
+        return;
+    }
+    activePointerId = null;
+    isMouseDown = false;
+}
+
+// Pointer events cover mouse, touch and pen; only the first active pointer steers
+canvas.addEventListener("pointerdown", (event) => {
+    if (activePointerId !== null) {
+        return;
+    }
+    event.preventDefault();
+    activePointerId = event.pointerId;
+    canvas.setPointerCapture(event.pointerId);
+    updatePointer(event);
 
     if (world && isMouseDown == false) {
         send_input_click_pressed(mouseX, mouseY);
-        console.log("Click Pressed");
     }
 
     isMouseDown = true;
 });
 
-window.addEventListener("mouseup", () => {
-    isMouseDown = false;
+canvas.addEventListener("pointermove", (event) => {
+    if (activePointerId !== null && event.pointerId !== activePointerId) {
+        return;
+    }
+    updatePointer(event);
+    if (world) {
+        world.pointer_moved(mouseX, mouseY);
+    }
 });
 
-canvas.addEventListener("mousemove", (event) => {
-    const rect = canvas.getBoundingClientRect();
-    mouseX = event.clientX - rect.left;
-    mouseY = event.clientY - rect.top;
+canvas.addEventListener("pointerup", releasePointer);
+canvas.addEventListener("pointercancel", releasePointer);
+
+window.addEventListener("resize", resizeCanvas);
+
+canvas.addEventListener("wheel", (event) => {
+    event.preventDefault();
+    if (world) {
+        world.set_camera_zoom(world.camera_zoom() * Math.exp(-event.deltaY * 0.001));
+    }
+}, { passive: false });
+
+window.addEventListener("keydown", (event) => {
+    if (world && !event.repeat && world.key_down(event.code)) {
+        event.preventDefault();
+    }
+});
+
+window.addEventListener("keyup", (event) => {
+    if (world && world.key_up(event.code)) {
+        event.preventDefault();
+    }
 });
 
 window.addEventListener("blur", () => {
+    activePointerId = null;
     isMouseDown = false;
+    if (world) {
+        world.input_blur();
+    }
 });
 
 document.addEventListener("mouseleave", () => {
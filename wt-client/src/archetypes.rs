@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use hecs::{Entity, World};
+use crate::components::*;
+use crate::sprite::{Direction, PLAYER_SHEET};
+
+// Must match the server's `Archetype` values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Archetype {
+    Player,
+    Projectile,
+}
+
+impl Archetype {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Archetype::Player),
+            1 => Some(Archetype::Projectile),
+            _ => None,
+        }
+    }
+}
+
+// Spawns the client-side bundle for an archetype; replicated components are applied on top afterwards
+type Spawn = fn(&mut World) -> Entity;
+
+#[derive(Default)]
+pub struct ArchetypeRegistry {
+    spawners: HashMap<Archetype, Spawn>,
+}
+
+impl ArchetypeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, archetype: Archetype, spawn: Spawn) -> &mut Self {
+        self.spawners.insert(archetype, spawn);
+        self
+    }
+
+    pub fn spawn(&self, world: &mut World, archetype: Archetype) -> Option<Entity> {
+        self.spawners.get(&archetype).map(|spawn| spawn(world))
+    }
+}
+
+fn spawn_player(world: &mut World) -> Entity {
+    world.spawn((
+        Player,
+        Position { x: 0.0, y: 0.0 },
+        State { state: PlayerState::Idle },
+        Facing { angle: std::f32::consts::FRAC_PI_2 },
+        Velocity { x: 0.0, y: 0.0 },
+        Health { current: 1, max: 1 },
+        Sprite { sheet: PLAYER_SHEET.to_string(), state: PlayerState::Idle, direction: Direction::Down, started_tick: 0 },
+        PlayerCollision { radius: 16.0, offset_x: 0.0, offset_y: 0.0 },
+    ))
+}
+
+fn spawn_projectile(world: &mut World) -> Entity {
+    world.spawn((
+        // Replaced by the server's radius from the spawn snapshot
+        Projectile { radius: 0.0 },
+        Position { x: 0.0, y: 0.0 },
+        Velocity { x: 0.0, y: 0.0 },
+    ))
+}
+
+pub fn default_archetypes() -> ArchetypeRegistry {
+    let mut registry = ArchetypeRegistry::new();
+    registry
+        .register(Archetype::Player, spawn_player)
+        .register(Archetype::Projectile, spawn_projectile);
+    registry
+}
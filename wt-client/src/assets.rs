@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use wasm_bindgen::JsValue;
+use web_sys::HtmlImageElement;
+use hecs::World;
+use crate::components::*;
+use crate::sprite::{SpriteSheet, PLAYER_SHEET};
+
+#[derive(Default)]
+pub struct Assets {
+    images: HashMap<String, HtmlImageElement>,
+    sprite_sheets: HashMap<String, SpriteSheet>,
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_default_sprites() -> Result<Self, JsValue> {
+        let mut assets = Self::new();
+        assets.add_sprite_sheet(PLAYER_SHEET, SpriteSheet::player_default())?;
+        Ok(assets)
+    }
+
+    pub fn add_sprite_sheet(&mut self, name: &str, sheet: SpriteSheet) -> Result<(), JsValue> {
+        self.load_image(&sheet.image)?;
+        self.sprite_sheets.insert(name.to_string(), sheet);
+        Ok(())
+    }
+
+    pub fn sprite_sheet(&self, name: &str) -> Option<&SpriteSheet> {
+        self.sprite_sheets.get(name)
+    }
+
+    pub fn sprite_sheet_mut(&mut self, name: &str) -> Option<&mut SpriteSheet> {
+        self.sprite_sheets.get_mut(name)
+    }
+
+    pub fn load_image(&mut self, path: &str) -> Result<(), JsValue> {
+        if self.images.contains_key(path) {
+            return Ok(());
+        }
+
+        let image = HtmlImageElement::new()?;
+        image.set_src(path);
+        self.images.insert(path.to_string(), image);
+        Ok(())
+    }
+
+    pub fn image(&self, path: &str) -> Option<&HtmlImageElement> {
+        // Images load asynchronously, so anything still loading is skipped this frame
+        self.images.get(path).filter(|image| image.complete() && image.natural_width() > 0)
+    }
+
+    pub fn load_tilesets(&mut self, world: &World) -> Result<(), JsValue> {
+        for (_, tileset) in world.query::<&Tileset>().iter() {
+            self.load_image(&tileset.image)?;
+        }
+        Ok(())
+    }
+}
@@ -1,24 +1,161 @@
-use uuid::Uuid;
+use hecs::Entity;
+use crate::sprite::Direction;
 
 #[derive(Debug)]
 pub struct Tick {
     pub tick: u64,
 }
 
+// Latest tick received from the server; datagrams can arrive out of order so it only moves forward
+#[derive(Debug)]
+pub struct ServerTick {
+    pub tick: u64,
+}
+
 #[derive(Debug)]
 pub struct Player;
 
 #[derive(Debug)]
-pub struct Position {
+pub struct LocalPlayer;
+
+#[derive(Debug)]
+pub struct Projectile {
+    pub radius: f32,
+}
+
+#[derive(Debug)]
+pub struct Health {
+    pub current: u16,
+    pub max: u16,
+}
+
+#[derive(Debug)]
+pub struct Team {
+    pub id: u8,
+}
+
+#[derive(Debug)]
+pub struct HitFlash {
+    pub started_tick: u64,
+}
+
+#[derive(Debug)]
+pub struct HitMarker {
+    pub x: f32,
+    pub y: f32,
+    pub started_tick: u64,
+}
+
+// `x`/`y` is the world point at the centre of the screen; `smoothing` is the fraction of the
+// remaining distance to the target covered each frame
+#[derive(Debug)]
+pub struct Camera {
+    pub x: f32,
+    pub y: f32,
+    pub zoom: f32,
+    pub smoothing: f32,
+    pub target: Option<Entity>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlayerState {
+    Idle,
+    Move,
+}
+
+impl PlayerState {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(PlayerState::Idle),
+            1 => Some(PlayerState::Move),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "idle" => Some(PlayerState::Idle),
+            "move" => Some(PlayerState::Move),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchPhase {
+    Warmup,
+    Countdown,
+    InRound,
+    RoundEnd,
+    MatchEnd,
+}
+
+impl MatchPhase {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(MatchPhase::Warmup),
+            1 => Some(MatchPhase::Countdown),
+            2 => Some(MatchPhase::InRound),
+            3 => Some(MatchPhase::RoundEnd),
+            4 => Some(MatchPhase::MatchEnd),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TeamScore {
+    pub wins: u8,
+    pub eliminations: u16,
+}
+
+// Singleton mirror of the server's match; `scores` is indexed by team id
+#[derive(Debug)]
+pub struct MatchState {
+    pub phase: MatchPhase,
+    pub phase_end_tick: Option<u64>,
+    pub round: u8,
+    pub round_count: u8,
+    pub tick_rate: u16,
+    pub round_winner: Option<u8>,
+    pub winner: Option<u8>,
+    pub scores: Vec<TeamScore>,
+}
+
+#[derive(Debug)]
+pub struct State {
+    pub state: PlayerState,
+}
+
+#[derive(Debug)]
+pub struct Velocity {
     pub x: f32,
     pub y: f32,
 }
 
 #[derive(Debug)]
-pub struct Connection {
-    pub connection_id: Uuid,
+pub struct Facing {
+    pub angle: f32,
+}
+
+// `started_tick` restarts whenever the state or direction changes so each animation begins on frame 0
+#[derive(Debug)]
+pub struct Sprite {
+    pub sheet: String,
+    pub state: PlayerState,
+    pub direction: Direction,
+    pub started_tick: u64,
 }
 
+#[derive(Debug)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NetworkId(pub u32);
+
 #[derive(Debug)]
 pub struct PlayerCollision {
     pub radius: f32,
@@ -34,7 +171,53 @@ pub struct CollisionLine {
     pub y2: f32,
 }
 
+#[derive(Debug)]
+pub struct MapBounds {
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct Region {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug)]
+pub struct Tileset {
+    pub first_gid: u32,
+    pub image: String,
+    pub columns: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+}
+
+#[derive(Debug)]
+pub struct TileLayer {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub data: Vec<u32>,
+}
+
 #[derive(Debug)]
 pub struct Collision {
     pub collision_lines: Vec<CollisionLine>, 
 }
+
+#[derive(Debug)]
+pub struct CollisionCircle {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+}
+
+#[derive(Debug)]
+pub struct CollisionPolygon {
+    pub points: Vec<(f32, f32)>,
+}
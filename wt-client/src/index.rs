@@ -0,0 +1,26 @@
+use std::collections::HashMap;
+use hecs::Entity;
+use crate::components::NetworkId;
+
+#[derive(Debug, Default)]
+pub struct NetworkIndex {
+    entities: HashMap<NetworkId, Entity>,
+}
+
+impl NetworkIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, network_id: NetworkId, entity: Entity) {
+        self.entities.insert(network_id, entity);
+    }
+
+    pub fn remove(&mut self, network_id: NetworkId) -> Option<Entity> {
+        self.entities.remove(&network_id)
+    }
+
+    pub fn get(&self, network_id: NetworkId) -> Option<Entity> {
+        self.entities.get(&network_id).copied()
+    }
+}
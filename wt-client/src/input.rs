@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+use wasm_bindgen::JsCast;
+use web_sys::{Gamepad, GamepadButton};
+
+pub const BUTTON_PRIMARY: u8 = 1 << 0;
+pub const BUTTON_POINTER: u8 = 1 << 1;
+pub const BUTTON_THROW: u8 = 1 << 2;
+
+const GAMEPAD_DEADZONE: f32 = 0.2;
+// Commands are datagrams and the server only acts on button edges, so a change is sent this many
+// ticks in a row rather than once and lost with a single dropped datagram
+const BUTTON_RESEND_TICKS: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Primary,
+    Throw,
+}
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "move_up" => Some(Action::MoveUp),
+            "move_down" => Some(Action::MoveDown),
+            "move_left" => Some(Action::MoveLeft),
+            "move_right" => Some(Action::MoveRight),
+            "primary" => Some(Action::Primary),
+            "throw" => Some(Action::Throw),
+            _ => None,
+        }
+    }
+}
+
+// Keys are bound by `KeyboardEvent.code` so layouts don't move them around
+pub struct InputBindings {
+    keys: HashMap<String, Action>,
+    gamepad_buttons: HashMap<u32, Action>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let keys = [
+            ("KeyW", Action::MoveUp),
+            ("KeyS", Action::MoveDown),
+            ("KeyA", Action::MoveLeft),
+            ("KeyD", Action::MoveRight),
+            ("ArrowUp", Action::MoveUp),
+            ("ArrowDown", Action::MoveDown),
+            ("ArrowLeft", Action::MoveLeft),
+            ("ArrowRight", Action::MoveRight),
+            ("Space", Action::Primary),
+            ("KeyF", Action::Throw),
+        ];
+
+        // Standard gamepad mapping: A and X buttons and the d-pad
+        let gamepad_buttons = [
+            (0, Action::Primary),
+            (2, Action::Throw),
+            (12, Action::MoveUp),
+            (13, Action::MoveDown),
+            (14, Action::MoveLeft),
+            (15, Action::MoveRight),
+        ];
+
+        Self {
+            keys: keys.into_iter().map(|(code, action)| (code.to_string(), action)).collect(),
+            gamepad_buttons: gamepad_buttons.into_iter().collect(),
+        }
+    }
+}
+
+impl InputBindings {
+    pub fn bind_key(&mut self, code: &str, action: Action) {
+        self.keys.insert(code.to_string(), action);
+    }
+
+    pub fn unbind_key(&mut self, code: &str) {
+        self.keys.remove(code);
+    }
+
+    pub fn bind_gamepad_button(&mut self, button: u32, action: Action) {
+        self.gamepad_buttons.insert(button, action);
+    }
+
+    pub fn key(&self, code: &str) -> Option<Action> {
+        self.keys.get(code).copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InputCommand {
+    pub move_x: i8,
+    pub move_y: i8,
+    pub buttons: u8,
+    pub pointer_x: f32,
+    pub pointer_y: f32,
+    // Server tick the player was looking at, so the server can judge hits against that moment
+    pub view_tick: u64,
+}
+
+impl InputCommand {
+    pub fn is_moving(&self) -> bool {
+        self.move_x != 0 || self.move_y != 0
+    }
+}
+
+#[derive(Default)]
+pub struct InputState {
+    pub bindings: InputBindings,
+    keys_down: HashSet<String>,
+    pointer_x: f32,
+    pointer_y: f32,
+    pointer_down: bool,
+    last_sent: Option<InputCommand>,
+    resend_ticks: u8,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key_down(&mut self, code: &str) -> bool {
+        self.keys_down.insert(code.to_string());
+        self.bindings.key(code).is_some()
+    }
+
+    pub fn key_up(&mut self, code: &str) -> bool {
+        self.keys_down.remove(code);
+        self.bindings.key(code).is_some()
+    }
+
+    pub fn pointer_moved(&mut self, x: f32, y: f32) {
+        self.pointer_x = x;
+        self.pointer_y = y;
+    }
+
+    pub fn set_pointer_down(&mut self, down: bool) {
+        self.pointer_down = down;
+    }
+
+    pub fn clear(&mut self) {
+        self.keys_down.clear();
+        self.pointer_down = false;
+    }
+
+    pub fn sample(&self) -> InputCommand {
+        let mut actions: HashSet<Action> = self.keys_down.iter()
+            .filter_map(|code| self.bindings.key(code))
+            .collect();
+
+        let (mut axis_x, mut axis_y) = (0.0f32, 0.0f32);
+        if let Some(gamepad) = first_gamepad() {
+            let buttons = gamepad.buttons();
+            for (&button, &action) in &self.bindings.gamepad_buttons {
+                let pressed = buttons.get(button)
+                    .dyn_into::<GamepadButton>()
+                    .is_ok_and(|button| button.pressed());
+                if pressed {
+                    actions.insert(action);
+                }
+            }
+
+            let axes = gamepad.axes();
+            axis_x = axes.get(0).as_f64().unwrap_or(0.0) as f32;
+            axis_y = axes.get(1).as_f64().unwrap_or(0.0) as f32;
+            if (axis_x * axis_x + axis_y * axis_y).sqrt() < GAMEPAD_DEADZONE {
+                axis_x = 0.0;
+                axis_y = 0.0;
+            }
+        }
+
+        let digital_x = actions.contains(&Action::MoveRight) as i8 as f32 - actions.contains(&Action::MoveLeft) as i8 as f32;
+        let digital_y = actions.contains(&Action::MoveDown) as i8 as f32 - actions.contains(&Action::MoveUp) as i8 as f32;
+        let move_x = (axis_x + digital_x).clamp(-1.0, 1.0);
+        let move_y = (axis_y + digital_y).clamp(-1.0, 1.0);
+
+        let mut buttons = 0;
+        if actions.contains(&Action::Primary) {
+            buttons |= BUTTON_PRIMARY;
+        }
+        if self.pointer_down {
+            buttons |= BUTTON_POINTER;
+        }
+        if actions.contains(&Action::Throw) {
+            buttons |= BUTTON_THROW;
+        }
+
+        InputCommand {
+            move_x: (move_x * 127.0).round() as i8,
+            move_y: (move_y * 127.0).round() as i8,
+            buttons,
+            pointer_x: self.pointer_x,
+            pointer_y: self.pointer_y,
+            view_tick: 0,
+        }
+    }
+
+    // Commands go out every tick while moving and for a few ticks whenever anything else changes
+    pub fn next_command(&mut self) -> Option<InputCommand> {
+        let command = self.sample();
+        if self.last_sent.is_none_or(|last| last.buttons != command.buttons || last.is_moving()) {
+            self.resend_ticks = BUTTON_RESEND_TICKS;
+        }
+        if command.is_moving() || self.resend_ticks > 0 {
+            self.resend_ticks = self.resend_ticks.saturating_sub(1);
+            self.last_sent = Some(command);
+            return Some(command);
+        }
+        None
+    }
+}
+
+fn first_gamepad() -> Option<Gamepad> {
+    let gamepads = web_sys::window()?.navigator().get_gamepads().ok()?;
+    gamepads.iter()
+        .filter_map(|gamepad| gamepad.dyn_into::<Gamepad>().ok())
+        .find(|gamepad| gamepad.connected())
+}
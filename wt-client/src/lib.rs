@@ -3,6 +3,13 @@ mod systems;
 mod render;
 mod world;
 mod network;
+mod index;
+mod assets;
+mod input;
+mod viewport;
+mod sprite;
+mod replication;
+mod archetypes;
 
 pub use world::WorldWrapper;
 
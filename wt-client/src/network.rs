@@ -1,6 +1,10 @@
-use uuid::Uuid;
-use hecs::World;
+use hecs::{Entity, World};
 use log::info;
+use crate::components::*;
+use crate::archetypes::{Archetype, ArchetypeRegistry};
+use crate::index::NetworkIndex;
+use crate::input::InputCommand;
+use crate::replication::ReplicationRegistry;
 use crate::systems::*;
 
 
@@ -9,77 +13,284 @@ use crate::systems::*;
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ServerToClientMessage {
     Tick = 0,
-    CreatePlayer = 1,
-    UpdatePlayerPosition = 2,
+    SpawnEntity = 1,
+    UpdateEntity = 2,
+    DespawnEntity = 3,
+    MapData = 4,
+    Hit = 5,
+    Eliminated = 6,
+    MatchState = 7,
 }
 
 impl ServerToClientMessage {
     pub fn from_u8(value: u8) -> Option<Self> {
         match value {
             0 => Some(ServerToClientMessage::Tick),
-            1 => Some(ServerToClientMessage::CreatePlayer),
-            2 => Some(ServerToClientMessage::UpdatePlayerPosition),
+            1 => Some(ServerToClientMessage::SpawnEntity),
+            2 => Some(ServerToClientMessage::UpdateEntity),
+            3 => Some(ServerToClientMessage::DespawnEntity),
+            4 => Some(ServerToClientMessage::MapData),
+            5 => Some(ServerToClientMessage::Hit),
+            6 => Some(ServerToClientMessage::Eliminated),
+            7 => Some(ServerToClientMessage::MatchState),
             _ => None,
         }
     }
 }
 
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(data: &'a [u8], offset: usize) -> Self {
+        Self { data, offset }
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset + len)?;
+        self.offset += len;
+        Some(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        self.bytes(4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        self.bytes(8).map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn f32(&mut self) -> Option<f32> {
+        self.bytes(4).map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+}
+
 pub fn decode_tick_datagram(data: &[u8], world: &mut World) {
-    if data.len() < 9 {
-        return;
+    if data.len() >= 9 {
+        let tick_bytes: [u8; 8] = data[1..9].try_into().unwrap();
+        let tick = u64::from_le_bytes(tick_bytes);
+
+        update_server_tick(world, tick);
+    }
+}
+
+const SPAWN_FLAG_LOCAL: u8 = 1 << 0;
+
+pub fn decode_spawn_entity_datagram(
+    data: &[u8],
+    world: &mut World,
+    index: &mut NetworkIndex,
+    archetypes: &ArchetypeRegistry,
+    registry: &ReplicationRegistry,
+) -> Option<()> {
+    let mut reader = ByteReader::new(data, 1);
+
+    let network_id = NetworkId(reader.u32()?);
+    let archetype = Archetype::from_u8(reader.u8()?)?;
+    let flags = reader.u8()?;
+
+    let entity = spawn_entity(world, index, archetypes, network_id, archetype)?;
+    apply_component_records(&mut reader, world, entity, registry)?;
+
+    if flags & SPAWN_FLAG_LOCAL != 0 {
+        set_local_player(world, entity);
     }
 
-    //let tick_bytes: [u8; 8] = data[1..9].try_into().unwrap();
-    //let tick = u64::from_be_bytes(tick_bytes);
+    info!("Entity {} Spawned : {:?}", network_id.0, archetype);
+    Some(())
+}
+
+pub fn decode_update_entity_datagram(data: &[u8], world: &mut World, index: &NetworkIndex, registry: &ReplicationRegistry) -> Option<()> {
+    let mut reader = ByteReader::new(data, 1);
+
+    let network_id = NetworkId(reader.u32()?);
+    let entity = index.get(network_id)?;
 
-    // Update tick in ECS TODO
+    apply_component_records(&mut reader, world, entity, registry)
 }
 
-pub fn decode_create_player_datagram(data: &[u8], world: &mut World) {
-    if data.len() < 1 + 16 + 4 + 4 {
-        return;
+// Each component record is `id, length, payload`; the length lets unknown ids be skipped
+fn apply_component_records(reader: &mut ByteReader, world: &mut World, entity: Entity, registry: &ReplicationRegistry) -> Option<()> {
+    let count = reader.u8()?;
+    for _ in 0..count {
+        let id = reader.u8()?;
+        let length = reader.u8()? as usize;
+        let payload = reader.bytes(length)?;
+        registry.apply(world, entity, id, payload);
     }
 
-    let uuid_bytes = &data[1..17];
-    let uuid = match Uuid::from_slice(uuid_bytes) {
-        Ok(id) => id,
-        Err(_) => {
-            return;
-        }
-    };
+    Some(())
+}
 
-    let x = f32::from_le_bytes(data[17..21].try_into().unwrap());
-    let y = f32::from_le_bytes(data[21..25].try_into().unwrap());
-    
-    info!("Player {} Created : ({}, {})", uuid, x, y);
+pub fn decode_despawn_entity_datagram(data: &[u8], world: &mut World, index: &mut NetworkIndex) -> Option<()> {
+    let mut reader = ByteReader::new(data, 1);
+
+    let network_id = NetworkId(reader.u32()?);
+
+    info!("Entity {} Despawned", network_id.0);
+
+    despawn_entity(world, index, network_id);
+    Some(())
+}
+
+pub fn decode_hit_datagram(data: &[u8], world: &mut World, index: &NetworkIndex) -> Option<()> {
+    let mut reader = ByteReader::new(data, 1);
+
+    let target = NetworkId(reader.u32()?);
+    let attacker = NetworkId(reader.u32()?);
+    let x = reader.f32()?;
+    let y = reader.f32()?;
+
+    info!("Entity {} Hit by {} : ({}, {})", target.0, attacker.0, x, y);
+
+    show_hit(world, index, target, x, y);
+    Some(())
+}
 
-    create_player(world, uuid, x, y);
+pub fn decode_eliminated_datagram(data: &[u8]) -> Option<()> {
+    let mut reader = ByteReader::new(data, 1);
+
+    let target = NetworkId(reader.u32()?);
+    let attacker = NetworkId(reader.u32()?);
+
+    // Health replication fades the player out; the event itself is only reported for now
+    info!("Entity {} Eliminated by {}", target.0, attacker.0);
+    Some(())
+}
+
+pub fn decode_match_state_datagram(data: &[u8], world: &mut World) -> Option<()> {
+    let mut reader = ByteReader::new(data, 1);
+
+    let phase = MatchPhase::from_u8(reader.u8()?)?;
+    let round = reader.u8()?;
+    let round_count = reader.u8()?;
+    let tick_rate = reader.u16()?.max(1);
+    let phase_end_tick = Some(reader.u64()?).filter(|&tick| tick != 0);
+    let round_winner = Some(reader.u8()?).filter(|&team| team != u8::MAX);
+    let winner = Some(reader.u8()?).filter(|&team| team != u8::MAX);
+
+    let team_count = reader.u8()?;
+    let mut scores = Vec::with_capacity(team_count as usize);
+    for _ in 0..team_count {
+        scores.push(TeamScore { wins: reader.u8()?, eliminations: reader.u16()? });
+    }
+
+    info!("Match {:?}, round {}/{}", phase, round, round_count);
+
+    set_match_state(world, MatchState { phase, phase_end_tick, round, round_count, tick_rate, round_winner, winner, scores });
+    Some(())
 }
 
-pub fn decode_update_player_position_datagram(data: &[u8], world: &mut World) {
-    if data.len() < 1 + 16 + 4 + 4 {
-        return;
+pub fn decode_map_datagram(data: &[u8], world: &mut World) -> Option<(f32, f32)> {
+    let mut reader = ByteReader::new(data, 1);
+
+    let width = reader.f32()?;
+    let height = reader.f32()?;
+
+    let mut collision_lines = Vec::new();
+    let mut polygons = Vec::new();
+    let polyline_count = reader.u16()?;
+    for _ in 0..polyline_count {
+        let closed = reader.u8()? != 0;
+        let point_count = reader.u16()?;
+        let mut points = Vec::with_capacity(point_count as usize);
+        for _ in 0..point_count {
+            points.push((reader.f32()?, reader.f32()?));
+        }
+
+        if closed {
+            polygons.push(CollisionPolygon { points });
+        } else {
+            for pair in points.windows(2) {
+                collision_lines.push(CollisionLine { x1: pair[0].0, y1: pair[0].1, x2: pair[1].0, y2: pair[1].1 });
+            }
+        }
+    }
+
+    let mut circles = Vec::new();
+    let circle_count = reader.u16()?;
+    for _ in 0..circle_count {
+        circles.push(CollisionCircle { x: reader.f32()?, y: reader.f32()?, radius: reader.f32()? });
+    }
+
+    // Spawn points are only used by the server
+    let spawn_count = reader.u16()?;
+    reader.bytes(spawn_count as usize * 8)?;
+
+    let mut regions = Vec::new();
+    let region_count = reader.u16()?;
+    for _ in 0..region_count {
+        let name_len = reader.u8()?;
+        let name = String::from_utf8_lossy(reader.bytes(name_len as usize)?).into_owned();
+        regions.push(Region {
+            name,
+            x: reader.f32()?,
+            y: reader.f32()?,
+            width: reader.f32()?,
+            height: reader.f32()?,
+        });
+    }
+
+    let mut tilesets = Vec::new();
+    let tileset_count = reader.u16()?;
+    for _ in 0..tileset_count {
+        let first_gid = reader.u32()?;
+        let columns = reader.u16()? as u32;
+        let tile_width = reader.u16()? as u32;
+        let tile_height = reader.u16()? as u32;
+        let image_len = reader.u16()?;
+        let image = String::from_utf8_lossy(reader.bytes(image_len as usize)?).into_owned();
+        tilesets.push(Tileset { first_gid, image, columns, tile_width, tile_height });
     }
 
-    let uuid_bytes = &data[1..17];
-    let uuid = match Uuid::from_slice(uuid_bytes) {
-        Ok(id) => id,
-        Err(_) => {
-            return;
+    let mut tile_layers = Vec::new();
+    let layer_count = reader.u16()?;
+    for _ in 0..layer_count {
+        let layer_width = reader.u16()? as u32;
+        let layer_height = reader.u16()? as u32;
+        let tile_width = reader.u16()? as u32;
+        let tile_height = reader.u16()? as u32;
+        let mut layer_data = Vec::with_capacity((layer_width * layer_height) as usize);
+        for _ in 0..layer_width * layer_height {
+            layer_data.push(reader.u32()?);
         }
-    };
+        tile_layers.push(TileLayer { width: layer_width, height: layer_height, tile_width, tile_height, data: layer_data });
+    }
+
+    info!("Map received : {} x {}, {} collision lines, {} tile layers", width, height, collision_lines.len(), tile_layers.len());
 
-    let x = f32::from_le_bytes(data[17..21].try_into().unwrap());
-    let y = f32::from_le_bytes(data[21..25].try_into().unwrap());
+    load_map(world, MapData {
+        bounds: MapBounds { width, height },
+        collision_lines,
+        polygons,
+        circles,
+        regions,
+        tilesets,
+        tile_layers,
+    });
 
-    update_position(world, uuid, x, y);
+    Some((width, height))
 }
 
 
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 enum ClientToServerMessage {
     InputClickPressed = 0,
+    InputClickHold = 1,
+    InputClickReleased = 2,
+    InputCommand = 3,
 }
 
 impl ClientToServerMessage {
@@ -97,4 +308,34 @@ pub fn build_input_click_pressed(x: f32, y: f32) -> Vec<u8> {
     buffer.extend_from_slice(&y.to_le_bytes());
 
     buffer
-}
\ No newline at end of file
+}
+
+pub fn build_input_click_hold(x: f32, y: f32) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(1 + 4 + 4);
+
+    buffer.push(ClientToServerMessage::InputClickHold.to_u8());
+
+    buffer.extend_from_slice(&x.to_le_bytes());
+    buffer.extend_from_slice(&y.to_le_bytes());
+
+    buffer
+}
+
+pub fn build_input_click_released() -> Vec<u8> {
+    vec![ClientToServerMessage::InputClickReleased.to_u8()]
+}
+
+pub fn build_input_command(command: &InputCommand) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(1 + 1 + 1 + 1 + 4 + 4 + 8);
+
+    buffer.push(ClientToServerMessage::InputCommand.to_u8());
+
+    buffer.push(command.move_x as u8);
+    buffer.push(command.move_y as u8);
+    buffer.push(command.buttons);
+    buffer.extend_from_slice(&command.pointer_x.to_le_bytes());
+    buffer.extend_from_slice(&command.pointer_y.to_le_bytes());
+    buffer.extend_from_slice(&command.view_tick.to_le_bytes());
+
+    buffer
+}
@@ -1,15 +1,32 @@
 use wasm_bindgen::JsValue;
 use web_sys::CanvasRenderingContext2d;
+use crate::assets::Assets;
 use crate::components::*;
+use crate::systems::{HIT_FLASH_TICKS, HIT_MARKER_TICKS};
+use crate::viewport::Viewport;
 use hecs::World;
 
-pub fn render(world: &World, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+const VELOCITY_INDICATOR_SCALE: f32 = 8.0;
+const TEAM_COLOURS: [&str; 4] = ["#E04848", "#4878E0", "#48C048", "#E0C048"];
+const TEAM_NAMES: [&str; 4] = ["Red", "Blue", "Green", "Yellow"];
+
+pub fn render(world: &World, assets: &Assets, viewport: &Viewport, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+    viewport.clear(context)?;
+    if let Some((_, camera)) = world.query::<&Camera>().iter().next() {
+        viewport.apply(context, camera)?;
+    }
+
     // Draw background
-    context.set_fill_style(&JsValue::from_str("#000000"));
-    context.fill_rect(0.0, 0.0, 512.0, 384.0);
+    let (width, height) = world.query::<&MapBounds>().iter()
+        .next()
+        .map_or((512.0, 384.0), |(_, bounds)| (f64::from(bounds.width), f64::from(bounds.height)));
+    context.set_fill_style_str("#000000");
+    context.fill_rect(0.0, 0.0, width, height);
+
+    render_tile_layers(world, assets, context)?;
     
     //Draw collision lines
-    context.set_stroke_style(&JsValue::from_str("#FFFFFF"));
+    context.set_stroke_style_str("#FFFFFF");
     for (_, collision) in world.query::<&Collision>().iter() {
         for line in &collision.collision_lines {
             context.begin_path();
@@ -19,37 +36,305 @@ pub fn render(world: &World, context: &CanvasRenderingContext2d) -> Result<(), J
         }
     }
 
-    // Draw player
-    context.set_stroke_style(&JsValue::from_str("#FFFFFF"));
-    context.set_fill_style(&JsValue::from_str("#FFFFFF"));
+    //Draw collision polygons
+    for (_, polygon) in world.query::<&CollisionPolygon>().iter() {
+        let Some(&(first_x, first_y)) = polygon.points.first() else {
+            continue;
+        };
+        context.begin_path();
+        context.move_to(f64::from(first_x), f64::from(first_y));
+        for &(x, y) in &polygon.points[1..] {
+            context.line_to(f64::from(x), f64::from(y));
+        }
+        context.close_path();
+        context.stroke();
+    }
+
+    //Draw collision circles
+    for (_, circle) in world.query::<&CollisionCircle>().iter() {
+        context.begin_path();
+        context.ellipse(
+            f64::from(circle.x), f64::from(circle.y),
+            f64::from(circle.radius), f64::from(circle.radius),
+            0.0, 0.0, std::f64::consts::PI * 2.0
+        )?;
+        context.stroke();
+    }
+
+    render_players(world, assets, context)?;
+    render_projectiles(world, context)?;
+    render_match_hud(world, viewport, context)?;
+
+    Ok(())
+}
+
+fn render_players(world: &World, assets: &Assets, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+    let mut tick_value = 0;
+    for (_, tick) in world.query::<&Tick>().iter() {
+        tick_value = tick.tick;
+    }
+
     for (_, (
         _,
         position,
-        collision
+        collision,
+        velocity,
+        health,
+        sprite,
+        hit_flash,
+        team
     )) in world.query::<(
         &Player,
         &Position,
-        &PlayerCollision
+        &PlayerCollision,
+        &Velocity,
+        &Health,
+        Option<&Sprite>,
+        Option<&HitFlash>,
+        Option<&Team>
     )>().iter() {
-        // Collision circle
+        // Eliminated players stay visible but faded until they respawn
+        context.set_global_alpha(if health.current == 0 { 0.3 } else { 1.0 });
+
+        // Team ring under the player, so teams read apart whatever the sprite looks like
+        let colour = team.map_or("#FFFFFF", |team| team_colour(team.id));
+        context.set_stroke_style_str(colour);
+        context.set_line_width(3.0);
         context.begin_path();
         context.ellipse(
-            (position.x + collision.offset_x) as f64,
-            (position.y + collision.offset_y) as f64,
-            collision.radius as f64,
-            collision.radius as f64,
+            f64::from(position.x + collision.offset_x), f64::from(position.y + collision.offset_y),
+            f64::from(collision.radius), f64::from(collision.radius),
             0.0, 0.0, std::f64::consts::PI * 2.0
         )?;
         context.stroke();
+        context.set_line_width(1.0);
+
+        context.set_stroke_style_str("#FFFFFF");
+        context.set_fill_style_str(colour);
+
+        if let Some(sprite) = sprite
+            && let Some(sheet) = assets.sprite_sheet(&sprite.sheet)
+            && let Some(image) = assets.image(&sheet.image)
+            && let Some(animation) = sheet.animation(sprite.state, sprite.direction)
+        {
+            let frame = animation.frame(tick_value.saturating_sub(sprite.started_tick));
+            let width = f64::from(sheet.frame_width);
+            let height = f64::from(sheet.frame_height);
+            context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                image,
+                f64::from(frame) * width, f64::from(animation.row) * height,
+                width, height,
+                f64::from(position.x) - width / 2.0, f64::from(position.y) - height / 2.0,
+                width, height,
+            )?;
+        } else {
+            render_player_debug(position, collision, velocity, context)?;
+        }
+
+        if let Some(hit_flash) = hit_flash {
+            let progress = tick_value.saturating_sub(hit_flash.started_tick) as f64 / HIT_FLASH_TICKS as f64;
+            context.set_global_alpha(0.6 * (1.0 - progress.min(1.0)));
+            context.set_fill_style_str("#FF0000");
+            context.begin_path();
+            context.ellipse(
+                f64::from(position.x + collision.offset_x), f64::from(position.y + collision.offset_y),
+                f64::from(collision.radius), f64::from(collision.radius),
+                0.0, 0.0, std::f64::consts::PI * 2.0
+            )?;
+            context.fill();
+        }
+
+        context.set_global_alpha(1.0);
+        render_health_bar(position, collision, health, context);
+    }
+
+    Ok(())
+}
+
+fn team_colour(team: u8) -> &'static str {
+    TEAM_COLOURS[team as usize % TEAM_COLOURS.len()]
+}
+
+fn team_name(team: u8) -> &'static str {
+    TEAM_NAMES[team as usize % TEAM_NAMES.len()]
+}
+
+fn render_match_hud(world: &World, viewport: &Viewport, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+    let mut match_query = world.query::<&MatchState>();
+    let Some((_, state)) = match_query.iter().next() else {
+        return Ok(());
+    };
+    let server_tick = world.query::<&ServerTick>().iter().next().map_or(0, |(_, tick)| tick.tick);
+    let seconds_left = state.phase_end_tick
+        .map(|end_tick| end_tick.saturating_sub(server_tick).div_ceil(u64::from(state.tick_rate)));
+
+    let headline = match state.phase {
+        MatchPhase::Warmup => "Warmup - waiting for players".to_string(),
+        MatchPhase::Countdown => format!("Round {} starts in {}", state.round, seconds_left.unwrap_or(0)),
+        MatchPhase::InRound => {
+            let seconds = seconds_left.unwrap_or(0);
+            format!("Round {}/{}  {}:{:02}", state.round, state.round_count, seconds / 60, seconds % 60)
+        }
+        MatchPhase::RoundEnd => match state.round_winner {
+            Some(team) => format!("{} wins round {}", team_name(team), state.round),
+            None => format!("Round {} drawn", state.round),
+        },
+        MatchPhase::MatchEnd => match state.winner {
+            Some(team) => format!("{} wins the match", team_name(team)),
+            None => "Match drawn".to_string(),
+        },
+    };
+
+    viewport.apply_screen(context)?;
+    let (width, _) = viewport.css_size();
+    let centre = f64::from(width) / 2.0;
+    context.set_font("bold 18px sans-serif");
+    context.set_text_align("center");
+    context.set_fill_style_str("#FFFFFF");
+    context.fill_text(&headline, centre, 28.0)?;
 
-        // Player center
+    if state.phase == MatchPhase::Warmup {
+        return Ok(());
+    }
+
+    // Round wins per team, with this round's eliminations alongside
+    context.set_font("16px sans-serif");
+    let spacing = 96.0;
+    let left = centre - spacing * (state.scores.len() as f64 - 1.0) / 2.0;
+    for (team, score) in state.scores.iter().enumerate() {
+        context.set_fill_style_str(team_colour(team as u8));
+        context.fill_text(&format!("{} ({})", score.wins, score.eliminations), left + spacing * team as f64, 50.0)?;
+    }
+
+    Ok(())
+}
+
+// Drawn while no sprite is available: collision circle, centre and movement intent
+fn render_player_debug(position: &Position, collision: &PlayerCollision, velocity: &Velocity, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+    context.begin_path();
+    context.ellipse(
+        (position.x + collision.offset_x) as f64,
+        (position.y + collision.offset_y) as f64,
+        collision.radius as f64,
+        collision.radius as f64,
+        0.0, 0.0, std::f64::consts::PI * 2.0
+    )?;
+    context.stroke();
+
+    // Player center
+    context.begin_path();
+    context.ellipse(
+        position.x as f64, position.y as f64,
+        4.0, 4.0, 0.0, 0.0, std::f64::consts::PI * 2.0
+    )?;
+    context.fill();
+
+    // Movement intent, scaled up so a few pixels per tick is visible
+    if velocity.x != 0.0 || velocity.y != 0.0 {
+        context.begin_path();
+        context.move_to(position.x as f64, position.y as f64);
+        context.line_to(
+            (position.x + velocity.x * VELOCITY_INDICATOR_SCALE) as f64,
+            (position.y + velocity.y * VELOCITY_INDICATOR_SCALE) as f64,
+        );
+        context.stroke();
+    }
+
+    Ok(())
+}
+
+fn render_health_bar(position: &Position, collision: &PlayerCollision, health: &Health, context: &CanvasRenderingContext2d) {
+    if health.max == 0 {
+        return;
+    }
+
+    let width = f64::from(collision.radius) * 2.0;
+    let x = f64::from(position.x + collision.offset_x) - width / 2.0;
+    let y = f64::from(position.y + collision.offset_y - collision.radius) - 8.0;
+    let fraction = f64::from(health.current) / f64::from(health.max);
+
+    context.set_fill_style_str("#440000");
+    context.fill_rect(x, y, width, 4.0);
+    context.set_fill_style_str("#44FF44");
+    context.fill_rect(x, y, width * fraction, 4.0);
+}
+
+fn render_projectiles(world: &World, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+    let mut tick_value = 0;
+    for (_, tick) in world.query::<&Tick>().iter() {
+        tick_value = tick.tick;
+    }
+
+    context.set_fill_style_str("#FFAA00");
+    for (_, (projectile, position)) in world.query::<(&Projectile, &Position)>().iter() {
         context.begin_path();
         context.ellipse(
-            position.x as f64, position.y as f64,
-            4.0, 4.0, 0.0, 0.0, std::f64::consts::PI * 2.0
+            f64::from(position.x), f64::from(position.y),
+            f64::from(projectile.radius), f64::from(projectile.radius),
+            0.0, 0.0, std::f64::consts::PI * 2.0
         )?;
         context.fill();
     }
 
+    // Hit markers grow and fade over their lifetime
+    for (_, marker) in world.query::<&HitMarker>().iter() {
+        let progress = tick_value.saturating_sub(marker.started_tick) as f64 / HIT_MARKER_TICKS as f64;
+        context.set_global_alpha(1.0 - progress.min(1.0));
+        context.set_stroke_style_str("#FF4444");
+        context.begin_path();
+        context.ellipse(
+            f64::from(marker.x), f64::from(marker.y),
+            8.0 + progress * 16.0, 8.0 + progress * 16.0,
+            0.0, 0.0, std::f64::consts::PI * 2.0
+        )?;
+        context.stroke();
+    }
+    context.set_global_alpha(1.0);
+
+    Ok(())
+}
+
+fn render_tile_layers(world: &World, assets: &Assets, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+    let mut tileset_query = world.query::<&Tileset>();
+    let mut tilesets: Vec<(u32, &Tileset)> = tileset_query.iter()
+        .map(|(_, tileset)| (tileset.first_gid, tileset))
+        .collect();
+    // Gids belong to the tileset with the highest first gid not above them
+    tilesets.sort_by_key(|(first_gid, _)| std::cmp::Reverse(*first_gid));
+
+    for (_, layer) in world.query::<&TileLayer>().iter() {
+        if layer.width == 0 {
+            continue;
+        }
+        for (i, &gid) in layer.data.iter().take((layer.width * layer.height) as usize).enumerate() {
+            if gid == 0 {
+                continue;
+            }
+            let Some(&(_, tileset)) = tilesets.iter().find(|(first_gid, _)| gid >= *first_gid) else {
+                continue;
+            };
+            let Some(image) = assets.image(&tileset.image) else {
+                continue;
+            };
+            if tileset.columns == 0 {
+                continue;
+            }
+
+            let local_id = gid - tileset.first_gid;
+            let source_x = (local_id % tileset.columns) * tileset.tile_width;
+            let source_y = (local_id / tileset.columns) * tileset.tile_height;
+            let tile_x = (i as u32 % layer.width) * layer.tile_width;
+            let tile_y = (i as u32 / layer.width) * layer.tile_height;
+
+            context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                image,
+                f64::from(source_x), f64::from(source_y),
+                f64::from(tileset.tile_width), f64::from(tileset.tile_height),
+                f64::from(tile_x), f64::from(tile_y),
+                f64::from(layer.tile_width), f64::from(layer.tile_height),
+            )?;
+        }
+    }
+
     Ok(())
 }
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use hecs::{Component, Entity, World};
+use crate::components::*;
+use crate::network::ByteReader;
+
+// Mirror of the server's `Replicate`; `ID` must match the server registration for the same component
+pub trait Replicate: Component + Sized {
+    const ID: u8;
+
+    fn decode(reader: &mut ByteReader) -> Option<Self>;
+}
+
+impl Replicate for Position {
+    const ID: u8 = 0;
+
+    fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(Position { x: reader.f32()?, y: reader.f32()? })
+    }
+}
+
+impl Replicate for State {
+    const ID: u8 = 1;
+
+    fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(State { state: PlayerState::from_u8(reader.u8()?)? })
+    }
+}
+
+impl Replicate for Facing {
+    const ID: u8 = 2;
+
+    fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(Facing { angle: reader.f32()? })
+    }
+}
+
+impl Replicate for Velocity {
+    const ID: u8 = 3;
+
+    fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(Velocity { x: reader.f32()?, y: reader.f32()? })
+    }
+}
+
+impl Replicate for Health {
+    const ID: u8 = 4;
+
+    fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(Health { current: reader.u16()?, max: reader.u16()? })
+    }
+}
+
+impl Replicate for Team {
+    const ID: u8 = 5;
+
+    fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(Team { id: reader.u8()? })
+    }
+}
+
+impl Replicate for Projectile {
+    const ID: u8 = 6;
+
+    fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(Projectile { radius: reader.f32()? })
+    }
+}
+
+type Apply = fn(&mut World, Entity, &[u8]) -> bool;
+
+#[derive(Default)]
+pub struct ReplicationRegistry {
+    components: HashMap<u8, Apply>,
+}
+
+impl ReplicationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: Replicate>(&mut self) -> &mut Self {
+        let previous = self.components.insert(T::ID, apply_component::<T>);
+        assert!(previous.is_none(), "replicated component id {} registered twice", T::ID);
+        self
+    }
+
+    // Unknown ids are skipped so an older client still applies the components it understands
+    pub fn apply(&self, world: &mut World, entity: Entity, id: u8, payload: &[u8]) -> bool {
+        match self.components.get(&id) {
+            Some(apply) => apply(world, entity, payload),
+            None => false,
+        }
+    }
+}
+
+fn apply_component<T: Replicate>(world: &mut World, entity: Entity, payload: &[u8]) -> bool {
+    let Some(component) = T::decode(&mut ByteReader::new(payload, 0)) else {
+        return false;
+    };
+    world.insert_one(entity, component).is_ok()
+}
+
+pub fn default_registry() -> ReplicationRegistry {
+    let mut registry = ReplicationRegistry::new();
+    registry
+        .register::<Position>()
+        .register::<State>()
+        .register::<Facing>()
+        .register::<Velocity>()
+        .register::<Health>()
+        .register::<Team>()
+        .register::<Projectile>();
+    registry
+}
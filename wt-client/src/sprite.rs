@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use crate::components::PlayerState;
+
+pub const PLAYER_SHEET: &str = "player";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Down,
+    Left,
+    Right,
+    Up,
+}
+
+impl Direction {
+    // Facing angles are radians from +x towards +y, so +y (down the screen) is a quarter turn
+    pub fn from_angle(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        if cos.abs() >= sin.abs() {
+            if cos >= 0.0 { Direction::Right } else { Direction::Left }
+        } else if sin >= 0.0 {
+            Direction::Down
+        } else {
+            Direction::Up
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "down" => Some(Direction::Down),
+            "left" => Some(Direction::Left),
+            "right" => Some(Direction::Right),
+            "up" => Some(Direction::Up),
+            _ => None,
+        }
+    }
+}
+
+// A run of frames along one row of the sheet
+#[derive(Debug, Clone, Copy)]
+pub struct Animation {
+    pub row: u32,
+    pub frames: u32,
+    pub ticks_per_frame: u32,
+}
+
+impl Animation {
+    pub fn frame(&self, elapsed_ticks: u64) -> u32 {
+        if self.frames == 0 {
+            return 0;
+        }
+        ((elapsed_ticks / u64::from(self.ticks_per_frame.max(1))) % u64::from(self.frames)) as u32
+    }
+}
+
+#[derive(Debug)]
+pub struct SpriteSheet {
+    pub image: String,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    animations: HashMap<(PlayerState, Direction), Animation>,
+}
+
+impl SpriteSheet {
+    pub fn new(image: &str, frame_width: u32, frame_height: u32) -> Self {
+        Self {
+            image: image.to_string(),
+            frame_width,
+            frame_height,
+            animations: HashMap::new(),
+        }
+    }
+
+    pub fn set_animation(&mut self, state: PlayerState, direction: Direction, animation: Animation) {
+        self.animations.insert((state, direction), animation);
+    }
+
+    pub fn animation(&self, state: PlayerState, direction: Direction) -> Option<&Animation> {
+        self.animations.get(&(state, direction))
+    }
+
+    // Served from client/public/sprites: rows 0-3 idle and 4-7 walking, each ordered down, left, right, up
+    pub fn player_default() -> Self {
+        let mut sheet = Self::new("sprites/player.png", 32, 32);
+        let directions = [Direction::Down, Direction::Left, Direction::Right, Direction::Up];
+        for (row, &direction) in directions.iter().enumerate() {
+            let row = row as u32;
+            sheet.set_animation(PlayerState::Idle, direction, Animation { row, frames: 2, ticks_per_frame: 15 });
+            sheet.set_animation(PlayerState::Move, direction, Animation { row: row + 4, frames: 4, ticks_per_frame: 6 });
+        }
+        sheet
+    }
+}
@@ -1,7 +1,12 @@
-use uuid::Uuid;
-use hecs::World;
+use hecs::{Entity, World};
 use crate::components::*;
-use log::info;
+use crate::archetypes::{Archetype, ArchetypeRegistry};
+use crate::index::NetworkIndex;
+use crate::sprite::Direction;
+use crate::viewport::Viewport;
+
+pub const HIT_MARKER_TICKS: u64 = 12;
+pub const HIT_FLASH_TICKS: u64 = 8;
 
 pub fn update_tick(world: &mut World) {
     for (_, tick) in world.query_mut::<&mut Tick>() {
@@ -9,28 +14,170 @@ pub fn update_tick(world: &mut World) {
     }
 }
 
-pub fn create_player(world: &mut World, connection_id: Uuid, x: f32, y: f32) {
-    world.spawn((
-        Player,
-        Connection {connection_id: connection_id},
-        Position { x, y },
-        PlayerCollision { radius: 16.0, offset_x: 0.0, offset_y: 0.0 },
-    ));
-}
-
-pub fn update_position(world: &mut World, connection_id: Uuid, x: f32, y: f32) {
-    for (_,(
-        _,
-        connection,
-        position,
-    )) in world.query_mut::<(
-        &Player,
-        &Connection,
-        &mut Position,
-    )>() {
-        if connection.connection_id == connection_id {
-            position.x = x;
-            position.y = y;
+pub fn update_server_tick(world: &mut World, tick: u64) {
+    for (_, server_tick) in world.query_mut::<&mut ServerTick>() {
+        server_tick.tick = server_tick.tick.max(tick);
+    }
+}
+
+pub fn set_match_state(world: &mut World, state: MatchState) {
+    let existing = world.query_mut::<&mut MatchState>().into_iter().next().map(|(entity, _)| entity);
+    match existing {
+        Some(entity) => {
+            let _ = world.insert_one(entity, state);
+        }
+        None => {
+            world.spawn((state,));
+        }
+    }
+}
+
+pub struct MapData {
+    pub bounds: MapBounds,
+    pub collision_lines: Vec<CollisionLine>,
+    pub polygons: Vec<CollisionPolygon>,
+    pub circles: Vec<CollisionCircle>,
+    pub regions: Vec<Region>,
+    pub tilesets: Vec<Tileset>,
+    pub tile_layers: Vec<TileLayer>,
+}
+
+pub fn load_map(world: &mut World, map: MapData) {
+    let previous: Vec<Entity> = world.query::<()>()
+        .with::<&MapBounds>().iter().map(|(entity, _)| entity)
+        .chain(world.query::<&Collision>().iter().map(|(entity, _)| entity))
+        .chain(world.query::<&CollisionPolygon>().iter().map(|(entity, _)| entity))
+        .chain(world.query::<&CollisionCircle>().iter().map(|(entity, _)| entity))
+        .chain(world.query::<&Region>().iter().map(|(entity, _)| entity))
+        .chain(world.query::<&Tileset>().iter().map(|(entity, _)| entity))
+        .chain(world.query::<&TileLayer>().iter().map(|(entity, _)| entity))
+        .collect();
+    for entity in previous {
+        let _ = world.despawn(entity);
+    }
+
+    world.spawn((map.bounds,));
+    world.spawn((Collision { collision_lines: map.collision_lines },));
+    for polygon in map.polygons {
+        world.spawn((polygon,));
+    }
+    for circle in map.circles {
+        world.spawn((circle,));
+    }
+    for region in map.regions {
+        world.spawn((region,));
+    }
+    for tileset in map.tilesets {
+        world.spawn((tileset,));
+    }
+    for layer in map.tile_layers {
+        world.spawn((layer,));
+    }
+}
+
+// A repeated spawn for the same id replaces the old entity rather than duplicating it
+pub fn spawn_entity(world: &mut World, index: &mut NetworkIndex, archetypes: &ArchetypeRegistry, network_id: NetworkId, archetype: Archetype) -> Option<Entity> {
+    if let Some(entity) = index.remove(network_id) {
+        let _ = world.despawn(entity);
+    }
+
+    let entity = archetypes.spawn(world, archetype)?;
+    index.insert(network_id, entity);
+    Some(entity)
+}
+
+pub fn despawn_entity(world: &mut World, index: &mut NetworkIndex, network_id: NetworkId) {
+    if let Some(entity) = index.remove(network_id) {
+        let _ = world.despawn(entity);
+    }
+}
+
+pub fn set_local_player(world: &mut World, entity: Entity) {
+    let _ = world.insert_one(entity, LocalPlayer);
+    let Some((x, y)) = world.get::<&Position>(entity).ok().map(|position| (position.x, position.y)) else {
+        return;
+    };
+
+    // Snap rather than glide so the first frame doesn't sweep across the map
+    for (_, camera) in world.query_mut::<&mut Camera>() {
+        camera.target = Some(entity);
+        camera.x = x;
+        camera.y = y;
+    }
+}
+
+pub fn show_hit(world: &mut World, index: &NetworkIndex, target: NetworkId, x: f32, y: f32) {
+    let tick_value = world.query::<&Tick>().iter().next().map_or(0, |(_, tick)| tick.tick);
+    world.spawn((HitMarker { x, y, started_tick: tick_value },));
+    if let Some(entity) = index.get(target) {
+        let _ = world.insert_one(entity, HitFlash { started_tick: tick_value });
+    }
+}
+
+pub fn expire_hit_markers(world: &mut World) {
+    let tick_value = world.query::<&Tick>().iter().next().map_or(0, |(_, tick)| tick.tick);
+    let expired: Vec<Entity> = world.query::<&HitMarker>().iter()
+        .filter(|(_, marker)| tick_value.saturating_sub(marker.started_tick) >= HIT_MARKER_TICKS)
+        .map(|(entity, _)| entity)
+        .collect();
+    for entity in expired {
+        let _ = world.despawn(entity);
+    }
+
+    let faded: Vec<Entity> = world.query::<&HitFlash>().iter()
+        .filter(|(_, flash)| tick_value.saturating_sub(flash.started_tick) >= HIT_FLASH_TICKS)
+        .map(|(entity, _)| entity)
+        .collect();
+    for entity in faded {
+        let _ = world.remove_one::<HitFlash>(entity);
+    }
+}
+
+pub fn update_animations(world: &mut World) {
+    let mut tick_value = 0;
+    for (_, tick) in world.query::<&Tick>().iter() {
+        tick_value = tick.tick;
+    }
+
+    for (_, (state, facing, sprite)) in world.query_mut::<(&State, &Facing, &mut Sprite)>() {
+        let direction = Direction::from_angle(facing.angle);
+        if sprite.state != state.state || sprite.direction != direction {
+            sprite.state = state.state;
+            sprite.direction = direction;
+            sprite.started_tick = tick_value;
         }
     }
-}
\ No newline at end of file
+}
+
+pub fn update_camera(world: &mut World, viewport: &Viewport) {
+    let bounds = world.query::<&MapBounds>().iter()
+        .next()
+        .map(|(_, bounds)| (bounds.width, bounds.height));
+
+    let mut cameras = world.query::<&mut Camera>();
+    for (_, camera) in cameras.iter() {
+        if let Some(target) = camera.target
+            && let Ok(position) = world.get::<&Position>(target)
+        {
+            camera.x += (position.x - camera.x) * camera.smoothing;
+            camera.y += (position.y - camera.y) * camera.smoothing;
+        }
+
+        let Some((width, height)) = bounds else {
+            continue;
+        };
+
+        // Keep the view inside the map, centring on any axis where the map is smaller than the view
+        let (visible_width, visible_height) = viewport.visible_size(camera);
+        camera.x = clamp_axis(camera.x, visible_width / 2.0, width);
+        camera.y = clamp_axis(camera.y, visible_height / 2.0, height);
+    }
+}
+
+fn clamp_axis(value: f32, half_extent: f32, size: f32) -> f32 {
+    if size <= half_extent * 2.0 {
+        size / 2.0
+    } else {
+        value.clamp(half_extent, size - half_extent)
+    }
+}
@@ -0,0 +1,93 @@
+use wasm_bindgen::JsValue;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+use crate::components::Camera;
+
+// Maps CSS pixels on the canvas element to world units around the camera
+#[derive(Debug)]
+pub struct Viewport {
+    css_width: f32,
+    css_height: f32,
+    pixel_ratio: f32,
+    view_width: f32,
+    view_height: f32,
+}
+
+impl Viewport {
+    // The reference view is the world area shown at zoom 1, fitted to whatever size the canvas has
+    pub fn new(view_width: f32, view_height: f32) -> Self {
+        Self {
+            css_width: view_width,
+            css_height: view_height,
+            pixel_ratio: 1.0,
+            view_width: view_width.max(1.0),
+            view_height: view_height.max(1.0),
+        }
+    }
+
+    pub fn resize(&mut self, canvas: &HtmlCanvasElement, css_width: f32, css_height: f32, pixel_ratio: f32) {
+        self.css_width = css_width.max(1.0);
+        self.css_height = css_height.max(1.0);
+        self.pixel_ratio = if pixel_ratio > 0.0 { pixel_ratio } else { 1.0 };
+
+        // The backing store is sized in device pixels so high-DPI screens stay sharp
+        canvas.set_width((self.css_width * self.pixel_ratio).round() as u32);
+        canvas.set_height((self.css_height * self.pixel_ratio).round() as u32);
+    }
+
+    // CSS pixels per world unit
+    pub fn scale(&self, camera: &Camera) -> f32 {
+        let fit = (self.css_width / self.view_width).min(self.css_height / self.view_height);
+        fit * camera.zoom
+    }
+
+    // Size of the world area currently on screen
+    pub fn visible_size(&self, camera: &Camera) -> (f32, f32) {
+        let scale = self.scale(camera);
+        (self.css_width / scale, self.css_height / scale)
+    }
+
+    pub fn screen_to_world(&self, camera: &Camera, x: f32, y: f32) -> (f32, f32) {
+        let scale = self.scale(camera);
+        (
+            (x - self.css_width / 2.0) / scale + camera.x,
+            (y - self.css_height / 2.0) / scale + camera.y,
+        )
+    }
+
+    pub fn clear(&self, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        context.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)?;
+        context.set_fill_style_str("#111111");
+        context.fill_rect(
+            0.0,
+            0.0,
+            f64::from(self.css_width * self.pixel_ratio),
+            f64::from(self.css_height * self.pixel_ratio),
+        );
+        Ok(())
+    }
+
+    // Draws in CSS pixels from the top-left of the canvas, for overlays that ignore the camera
+    pub fn apply_screen(&self, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        let pixel_ratio = f64::from(self.pixel_ratio);
+        context.set_transform(pixel_ratio, 0.0, 0.0, pixel_ratio, 0.0, 0.0)
+    }
+
+    pub fn css_size(&self) -> (f32, f32) {
+        (self.css_width, self.css_height)
+    }
+
+    pub fn apply(&self, context: &CanvasRenderingContext2d, camera: &Camera) -> Result<(), JsValue> {
+        let scale = self.scale(camera);
+        let offset_x = self.css_width / 2.0 - camera.x * scale;
+        let offset_y = self.css_height / 2.0 - camera.y * scale;
+        let device_scale = f64::from(scale * self.pixel_ratio);
+        context.set_transform(
+            device_scale,
+            0.0,
+            0.0,
+            device_scale,
+            f64::from(offset_x * self.pixel_ratio),
+            f64::from(offset_y * self.pixel_ratio),
+        )
+    }
+}
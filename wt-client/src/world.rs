@@ -5,14 +5,32 @@ use wasm_bindgen::JsCast;
 
 use hecs::World;
 
+use crate::assets::Assets;
 use crate::components::*;
+use crate::archetypes::{default_archetypes, ArchetypeRegistry};
+use crate::index::NetworkIndex;
+use crate::input::{Action, InputState};
 use crate::systems::*;
 use crate::render::*;
+use crate::replication::{default_registry, ReplicationRegistry};
+use crate::sprite::{Animation, Direction, SpriteSheet};
+use crate::viewport::Viewport;
 use crate::network::*;
 
+const CAMERA_SMOOTHING: f32 = 0.2;
+const MIN_CAMERA_ZOOM: f32 = 0.5;
+const MAX_CAMERA_ZOOM: f32 = 4.0;
+
 #[wasm_bindgen]
 pub struct WorldWrapper {
     world: World,
+    network_index: NetworkIndex,
+    archetypes: ArchetypeRegistry,
+    replication: ReplicationRegistry,
+    input: InputState,
+    assets: Assets,
+    viewport: Viewport,
+    canvas: HtmlCanvasElement,
     context: CanvasRenderingContext2d,
 }
 
@@ -34,25 +52,26 @@ impl WorldWrapper {
 
         let mut world = World::new();
         world.spawn((Tick { tick: 0 },));
-
-        world.spawn((
-            Collision {
-                collision_lines: vec![
-                    CollisionLine { x1: 192.0, y1: 128.0, x2: 320.0, y2: 128.0 },
-                    CollisionLine { x1: 320.0, y1: 128.0, x2: 320.0, y2: 256.0 },
-                    CollisionLine { x1: 320.0, y1: 256.0, x2: 296.0, y2: 208.0 },
-                    CollisionLine { x1: 296.0, y1: 208.0, x2: 248.0, y2: 256.0 },
-                ]
-            },
-        ));
-
-
-        Ok(WorldWrapper { world, context })
+        world.spawn((ServerTick { tick: 0 },));
+
+        let viewport = Viewport::new(canvas.width() as f32, canvas.height() as f32);
+        world.spawn((Camera {
+            x: canvas.width() as f32 / 2.0,
+            y: canvas.height() as f32 / 2.0,
+            zoom: 1.0,
+            smoothing: CAMERA_SMOOTHING,
+            target: None,
+        },));
+
+        Ok(WorldWrapper { world, network_index: NetworkIndex::new(), archetypes: default_archetypes(), replication: default_registry(), input: InputState::new(), assets: Assets::with_default_sprites()?, viewport, canvas, context })
     }
 
     pub fn update(&mut self) -> Result<(), JsValue> {
         update_tick(&mut self.world);
-        render(&self.world, &self.context)
+        expire_hit_markers(&mut self.world);
+        update_animations(&mut self.world);
+        update_camera(&mut self.world, &self.viewport);
+        render(&self.world, &self.assets, &self.viewport, &self.context)
     }
 
     pub fn receive_message(&mut self, data: &[u8]) {
@@ -66,12 +85,36 @@ impl WorldWrapper {
                 decode_tick_datagram(data, &mut self.world);
             }
 
-            Some(ServerToClientMessage::CreatePlayer) => {
-                decode_create_player_datagram(data, &mut self.world);
+            Some(ServerToClientMessage::SpawnEntity) => {
+                decode_spawn_entity_datagram(data, &mut self.world, &mut self.network_index, &self.archetypes, &self.replication);
+            }
+
+            Some(ServerToClientMessage::UpdateEntity) => {
+                decode_update_entity_datagram(data, &mut self.world, &self.network_index, &self.replication);
+            }
+
+            Some(ServerToClientMessage::MapData) => {
+                if decode_map_datagram(data, &mut self.world).is_some()
+                    && let Err(err) = self.assets.load_tilesets(&self.world)
+                {
+                    web_sys::console::warn_2(&"Failed to load tilesets".into(), &err);
+                }
+            }
+
+            Some(ServerToClientMessage::Hit) => {
+                decode_hit_datagram(data, &mut self.world, &self.network_index);
+            }
+
+            Some(ServerToClientMessage::Eliminated) => {
+                decode_eliminated_datagram(data);
+            }
+
+            Some(ServerToClientMessage::MatchState) => {
+                decode_match_state_datagram(data, &mut self.world);
             }
 
-            Some(ServerToClientMessage::UpdatePlayerPosition) => {
-                decode_update_player_position_datagram(data, &mut self.world);
+            Some(ServerToClientMessage::DespawnEntity) => {
+                decode_despawn_entity_datagram(data, &mut self.world, &mut self.network_index);
             }
 
             None => {
@@ -80,7 +123,108 @@ impl WorldWrapper {
         }
     }
 
+    pub fn resize(&mut self, css_width: f32, css_height: f32, pixel_ratio: f32) {
+        self.viewport.resize(&self.canvas, css_width, css_height, pixel_ratio);
+    }
+
+    pub fn set_camera_zoom(&mut self, zoom: f32) {
+        for (_, camera) in self.world.query_mut::<&mut Camera>() {
+            camera.zoom = zoom.clamp(MIN_CAMERA_ZOOM, MAX_CAMERA_ZOOM);
+        }
+    }
+
+    pub fn camera_zoom(&self) -> f32 {
+        self.world.query::<&Camera>().iter().next().map_or(1.0, |(_, camera)| camera.zoom)
+    }
+
+    // Pointer positions arrive in CSS pixels relative to the canvas and leave as world coordinates
     pub fn input_click_pressed(&mut self, x: f32, y: f32) -> Vec<u8> {
-        return build_input_click_pressed(x, y);
+        let (x, y) = self.screen_to_world(x, y);
+        self.input.pointer_moved(x, y);
+        self.input.set_pointer_down(true);
+        build_input_click_pressed(x, y)
+    }
+
+    pub fn input_click_hold(&mut self, x: f32, y: f32) -> Vec<u8> {
+        let (x, y) = self.screen_to_world(x, y);
+        self.input.pointer_moved(x, y);
+        build_input_click_hold(x, y)
+    }
+
+    pub fn input_click_released(&mut self) -> Vec<u8> {
+        self.input.set_pointer_down(false);
+        build_input_click_released()
+    }
+
+    pub fn key_down(&mut self, code: &str) -> bool {
+        self.input.key_down(code)
+    }
+
+    pub fn key_up(&mut self, code: &str) -> bool {
+        self.input.key_up(code)
+    }
+
+    pub fn pointer_moved(&mut self, x: f32, y: f32) {
+        let (x, y) = self.screen_to_world(x, y);
+        self.input.pointer_moved(x, y);
+    }
+
+    pub fn input_blur(&mut self) {
+        self.input.clear();
+    }
+
+    pub fn input_command(&mut self) -> Vec<u8> {
+        match self.input.next_command() {
+            Some(mut command) => {
+                command.view_tick = self.world.query::<&ServerTick>().iter().next().map_or(0, |(_, server_tick)| server_tick.tick);
+                build_input_command(&command)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub fn bind_key(&mut self, action: &str, code: &str) -> bool {
+        let Some(action) = Action::from_name(action) else {
+            return false;
+        };
+        self.input.bindings.bind_key(code, action);
+        true
+    }
+
+    pub fn unbind_key(&mut self, code: &str) {
+        self.input.bindings.unbind_key(code);
+    }
+
+    pub fn bind_gamepad_button(&mut self, action: &str, button: u32) -> bool {
+        let Some(action) = Action::from_name(action) else {
+            return false;
+        };
+        self.input.bindings.bind_gamepad_button(button, action);
+        true
+    }
+
+    pub fn define_sprite_sheet(&mut self, name: &str, image: &str, frame_width: u32, frame_height: u32) -> Result<(), JsValue> {
+        self.assets.add_sprite_sheet(name, SpriteSheet::new(image, frame_width, frame_height))
+    }
+
+    pub fn define_animation(&mut self, sheet: &str, state: &str, direction: &str, row: u32, frames: u32, ticks_per_frame: u32) -> bool {
+        let (Some(sheet), Some(state), Some(direction)) = (
+            self.assets.sprite_sheet_mut(sheet),
+            PlayerState::from_name(state),
+            Direction::from_name(direction),
+        ) else {
+            return false;
+        };
+        sheet.set_animation(state, direction, Animation { row, frames, ticks_per_frame });
+        true
+    }
+}
+
+impl WorldWrapper {
+    fn screen_to_world(&self, x: f32, y: f32) -> (f32, f32) {
+        match self.world.query::<&Camera>().iter().next() {
+            Some((_, camera)) => self.viewport.screen_to_world(camera, x, y),
+            None => (x, y),
+        }
     }
 }
@@ -0,0 +1,89 @@
+// Compares `CollisionGrid` queries against scanning every shape, on generated maps with many segments.
+// Run with `cargo bench --bench collision_grid`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use wt_server::broadphase::CollisionGrid;
+use wt_server::components::{CollisionCircle, CollisionLine, StaticShape};
+
+// Same values the server uses: `map.rs` cell size, and `handle_state`'s reach for a walking player
+const CELL_SIZE: f32 = 64.0;
+const QUERY_RADIUS: f32 = 16.0 * 5.0 + 2.0;
+const MAP_SIZE: f32 = 4096.0;
+const MAX_SEGMENT_LENGTH: f32 = 96.0;
+const QUERIES: usize = 10_000;
+const MIN_RUN_TIME: Duration = Duration::from_millis(500);
+
+fn generate_shapes(rng: &mut StdRng, segments: usize) -> Vec<StaticShape> {
+    let mut shapes: Vec<StaticShape> = (0..segments)
+        .map(|_| {
+            let x1 = rng.gen_range(0.0..MAP_SIZE);
+            let y1 = rng.gen_range(0.0..MAP_SIZE);
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let length = rng.gen_range(8.0..MAX_SEGMENT_LENGTH);
+            StaticShape::Line(CollisionLine { x1, y1, x2: x1 + angle.cos() * length, y2: y1 + angle.sin() * length })
+        })
+        .collect();
+    // A few circles, as maps mix both
+    shapes.extend((0..segments / 10).map(|_| StaticShape::Circle(CollisionCircle {
+        x: rng.gen_range(0.0..MAP_SIZE),
+        y: rng.gen_range(0.0..MAP_SIZE),
+        radius: rng.gen_range(8.0..32.0),
+    })));
+    shapes
+}
+
+// The pre-grid approach: every shape is bounds-checked on every query
+fn linear_query(shapes: &[StaticShape], x: f32, y: f32, radius: f32) -> Vec<&StaticShape> {
+    shapes.iter()
+        .filter(|shape| {
+            let (min_x, min_y, max_x, max_y) = match shape {
+                StaticShape::Line(line) => (line.x1.min(line.x2), line.y1.min(line.y2), line.x1.max(line.x2), line.y1.max(line.y2)),
+                StaticShape::Circle(circle) => (circle.x - circle.radius, circle.y - circle.radius, circle.x + circle.radius, circle.y + circle.radius),
+            };
+            max_x >= x - radius && min_x <= x + radius && max_y >= y - radius && min_y <= y + radius
+        })
+        .collect()
+}
+
+// Repeats `run` over all query points until `MIN_RUN_TIME` passes; returns nanoseconds per query
+fn time_per_query(points: &[(f32, f32)], mut run: impl FnMut(f32, f32) -> usize) -> f64 {
+    let started = Instant::now();
+    let mut rounds = 0u32;
+    while started.elapsed() < MIN_RUN_TIME {
+        for &(x, y) in points {
+            black_box(run(black_box(x), black_box(y)));
+        }
+        rounds += 1;
+    }
+    started.elapsed().as_nanos() as f64 / (f64::from(rounds) * points.len() as f64)
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(0x5eed);
+    let points: Vec<(f32, f32)> = (0..QUERIES)
+        .map(|_| (rng.gen_range(0.0..MAP_SIZE), rng.gen_range(0.0..MAP_SIZE)))
+        .collect();
+
+    println!("{:>9} {:>14} {:>14} {:>9}", "segments", "linear ns/q", "grid ns/q", "speedup");
+    for segments in [100, 1_000, 10_000, 50_000] {
+        let shapes = generate_shapes(&mut rng, segments);
+        let grid = CollisionGrid::new(CELL_SIZE, shapes.clone());
+
+        // The grid's candidates may include shapes from the edges of overlapping cells, but never miss one
+        for &(x, y) in points.iter().take(1_000) {
+            let candidates = grid.query_circle(x, y, QUERY_RADIUS);
+            for shape in linear_query(&shapes, x, y, QUERY_RADIUS) {
+                assert!(candidates.contains(&shape), "grid missed {shape:?} at ({x}, {y})");
+            }
+        }
+
+        let linear = time_per_query(&points, |x, y| linear_query(&shapes, x, y, QUERY_RADIUS).len());
+        let grid = time_per_query(&points, |x, y| grid.query_circle(x, y, QUERY_RADIUS).len());
+        println!("{:>9} {:>14.1} {:>14.1} {:>8.1}x", segments, linear, grid, linear / grid);
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use crate::components::StaticShape;
+
+type Cell = (i32, i32);
+
+// Static uniform grid over the map's collision shapes, built once when the map loads
+#[derive(Debug)]
+pub struct CollisionGrid {
+    cell_size: f32,
+    shapes: Vec<StaticShape>,
+    cells: HashMap<Cell, Vec<usize>>,
+}
+
+impl CollisionGrid {
+    pub fn new(cell_size: f32, shapes: Vec<StaticShape>) -> Self {
+        let mut grid = Self {
+            cell_size,
+            shapes,
+            cells: HashMap::new(),
+        };
+
+        for (index, shape) in grid.shapes.iter().enumerate() {
+            let (min_x, min_y, max_x, max_y) = match shape {
+                StaticShape::Line(line) => (line.x1.min(line.x2), line.y1.min(line.y2), line.x1.max(line.x2), line.y1.max(line.y2)),
+                StaticShape::Circle(circle) => (circle.x - circle.radius, circle.y - circle.radius, circle.x + circle.radius, circle.y + circle.radius),
+            };
+            let (min_cx, min_cy) = grid.cell(min_x, min_y);
+            let (max_cx, max_cy) = grid.cell(max_x, max_y);
+            for cx in min_cx..=max_cx {
+                for cy in min_cy..=max_cy {
+                    grid.cells.entry((cx, cy)).or_default().push(index);
+                }
+            }
+        }
+
+        grid
+    }
+
+    pub fn query(&self, min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Vec<&StaticShape> {
+        let (min_cx, min_cy) = self.cell(min_x, min_y);
+        let (max_cx, max_cy) = self.cell(max_x, max_y);
+
+        let mut indices = Vec::new();
+        for cx in min_cx..=max_cx {
+            for cy in min_cy..=max_cy {
+                if let Some(cell) = self.cells.get(&(cx, cy)) {
+                    indices.extend_from_slice(cell);
+                }
+            }
+        }
+
+        // Shapes spanning several cells show up once per cell
+        indices.sort_unstable();
+        indices.dedup();
+        indices.into_iter().map(|index| &self.shapes[index]).collect()
+    }
+
+    pub fn query_circle(&self, x: f32, y: f32, radius: f32) -> Vec<&StaticShape> {
+        self.query(x - radius, y - radius, x + radius, y + radius)
+    }
+
+    fn cell(&self, x: f32, y: f32) -> Cell {
+        ((x / self.cell_size).floor() as i32, (y / self.cell_size).floor() as i32)
+    }
+}
@@ -1,4 +1,7 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use hecs::Entity;
 use uuid::Uuid;
+use crate::replication::SentComponents;
 
 #[derive(Debug)]
 pub struct Tick {
@@ -13,11 +16,48 @@ pub struct Connection {
     pub connection_id: Uuid,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NetworkId(pub u32);
+
+// Tells the client which bundle to spawn for a networked entity
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Archetype {
+    Player = 0,
+    Projectile = 1,
+}
+
+impl Archetype {
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+#[derive(Debug)]
+pub struct Interest {
+    pub view_radius: f32,
+    pub visible: HashSet<NetworkId>,
+}
+
+// `last_sent` is what each visible entity looked like in the last update this connection was sent
+#[derive(Debug)]
+pub struct Replication {
+    pub bytes_per_tick: usize,
+    pub last_sent_tick: HashMap<NetworkId, u64>,
+    pub last_sent: HashMap<NetworkId, SentComponents>,
+}
+
 #[derive(Debug)]
 pub struct State {
     pub state: PlayerState,
 }
 
+// Radians, measured from +x towards +y; kept from the last non-zero velocity
+#[derive(Debug)]
+pub struct Facing {
+    pub angle: f32,
+}
+
 #[derive(Debug)]
 pub struct Position {
     pub x: f32,
@@ -36,13 +76,79 @@ pub struct MoveTarget {
     pub y: f32,
 }
 
+#[derive(Debug)]
+pub struct Path {
+    pub waypoints: VecDeque<(f32, f32)>,
+}
+
 #[derive(Debug)]
 pub struct PlayerMove {
     pub move_speed: f32,
     pub move_input_type: MovementType,
     pub timer: u8,
-    //pub timer_threshold: u8,
-    //pub direction_radius: f32
+    pub timer_threshold: u8,
+    pub direction_radius: f32,
+    pub last_hold_tick: u64,
+}
+
+// `aim` is set by a throw press and consumed by `throw_projectiles` once the cooldown allows;
+// `view_tick` is the server tick the thrower was seeing when they pressed
+#[derive(Debug)]
+pub struct PlayerThrow {
+    pub aim: Option<(f32, f32)>,
+    pub view_tick: u64,
+    pub ready_tick: u64,
+}
+
+// `rewind_ticks` is how far behind the present the thrower saw other players, so hits are
+// checked against where targets were on their screen rather than where they are now
+#[derive(Debug)]
+pub struct Projectile {
+    pub owner: Entity,
+    pub radius: f32,
+    pub expires_tick: u64,
+    pub bounces_remaining: u8,
+    pub rewind_ticks: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Team {
+    pub id: u8,
+}
+
+#[derive(Debug)]
+pub struct Health {
+    pub current: u16,
+    pub max: u16,
+}
+
+// Out of play until `respawn_tick`: can't move, throw, be hit or block other players
+#[derive(Debug)]
+pub struct Eliminated {
+    pub respawn_tick: u64,
+}
+
+// Recent positions, oldest first, one per tick
+#[derive(Debug)]
+pub struct PositionHistory {
+    pub samples: VecDeque<(u64, f32, f32)>,
+}
+
+impl PositionHistory {
+    // Falls back to the oldest sample when `tick` is older than the history reaches
+    pub fn position_at(&self, tick: u64) -> Option<(f32, f32)> {
+        self.samples.iter()
+            .rev()
+            .find(|(sample_tick, _, _)| *sample_tick <= tick)
+            .or_else(|| self.samples.front())
+            .map(|&(_, x, y)| (x, y))
+    }
+}
+
+#[derive(Debug)]
+pub struct PlayerInput {
+    pub buttons: u8,
+    pub moving: bool,
 }
 
 #[derive(Debug)]
@@ -52,7 +158,7 @@ pub struct PlayerCollision {
     pub offset_y: f32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct CollisionLine {
     pub x1: f32,
     pub y1: f32,
@@ -60,11 +166,111 @@ pub struct CollisionLine {
     pub y2: f32,
 }
 
+#[derive(Debug)]
+pub struct MapBounds {
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug)]
+pub struct SpawnPoint {
+    pub x: f32,
+    pub y: f32,
+    pub team: Option<u8>,
+}
+
+// Only players on `team` may stand inside; unrestricted regions have no effect on movement
+#[derive(Debug, Clone, Copy)]
+pub struct Region {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub team: Option<u8>,
+}
+
+#[derive(Debug)]
+pub struct SpawnRotation {
+    pub next: usize,
+}
+
 #[derive(Debug)]
 pub struct Collision {
     pub collision_lines: Vec<CollisionLine>, 
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionCircle {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+}
+
+#[derive(Debug)]
+pub struct CollisionPolygon {
+    pub points: Vec<(f32, f32)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StaticShape {
+    Line(CollisionLine),
+    Circle(CollisionCircle),
+}
+
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchPhase {
+    Warmup = 0,
+    Countdown = 1,
+    InRound = 2,
+    RoundEnd = 3,
+    MatchEnd = 4,
+}
+
+impl MatchPhase {
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    // Players hold still while the countdown runs
+    pub fn allows_movement(self) -> bool {
+        self != MatchPhase::Countdown
+    }
+
+    pub fn allows_damage(self) -> bool {
+        matches!(self, MatchPhase::Warmup | MatchPhase::InRound)
+    }
+
+    // Outside warmup an eliminated player sits out until the next round starts
+    pub fn allows_respawn(self) -> bool {
+        self == MatchPhase::Warmup
+    }
+}
+
+// Singleton. Per-team lists are indexed by team id; `changed` marks the state for the next broadcast.
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub phase: MatchPhase,
+    pub phase_end_tick: Option<u64>,
+    pub round: u8,
+    pub round_count: u8,
+    // Ticks per second, so clients can turn `phase_end_tick` into a countdown
+    pub tick_rate: u16,
+    pub round_wins: Vec<u8>,
+    pub round_eliminations: Vec<u16>,
+    pub round_winner: Option<u8>,
+    pub winner: Option<u8>,
+    pub changed: bool,
+}
+
+impl Match {
+    pub fn set_phase(&mut self, phase: MatchPhase, phase_end_tick: Option<u64>) {
+        self.phase = phase;
+        self.phase_end_tick = phase_end_tick;
+        self.changed = true;
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PlayerState {
@@ -72,9 +278,18 @@ pub enum PlayerState {
     Move,
 }
 
+impl PlayerState {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            PlayerState::Idle => 0,
+            PlayerState::Move => 1,
+        }
+    }
+}
+
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MovementType {
     Target,
-    //Direction,
+    Direction,
 }
\ No newline at end of file
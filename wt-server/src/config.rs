@@ -0,0 +1,117 @@
+use crate::spawn::SpawnPolicy;
+
+#[derive(Debug, Clone)]
+pub struct WorldConfig {
+    pub map_path: String,
+    pub tick_rate: u16,
+    pub view_radius: f32,
+    pub view_exit_margin: f32,
+    pub interest_cell_size: f32,
+    pub bandwidth_per_tick: usize,
+    pub self_priority: f32,
+    pub state_refresh_ticks: u64,
+    pub spawn_policy: SpawnPolicy,
+    pub projectile_speed: f32,
+    pub projectile_radius: f32,
+    pub projectile_lifetime_ticks: u64,
+    pub projectile_bounces: u8,
+    pub throw_cooldown_ticks: u64,
+    pub max_rewind_ticks: u64,
+    pub player_max_health: u16,
+    pub projectile_damage: u16,
+    pub respawn_ticks: u64,
+    pub team_count: u8,
+    pub friendly_fire: bool,
+    pub min_players: usize,
+    pub round_count: u8,
+    pub countdown_ticks: u64,
+    pub round_time_ticks: u64,
+    pub round_end_ticks: u64,
+    pub match_end_ticks: u64,
+    pub max_rooms: usize,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            map_path: "maps/default.json".to_string(),
+            tick_rate: 30,
+            view_radius: 320.0,
+            view_exit_margin: 32.0,
+            interest_cell_size: 128.0,
+            bandwidth_per_tick: 1200,
+            self_priority: 4.0,
+            state_refresh_ticks: 30,
+            spawn_policy: SpawnPolicy::Team,
+            projectile_speed: 8.0,
+            projectile_radius: 6.0,
+            projectile_lifetime_ticks: 60,
+            projectile_bounces: 1,
+            throw_cooldown_ticks: 15,
+            max_rewind_ticks: 10,
+            player_max_health: 3,
+            projectile_damage: 1,
+            respawn_ticks: 90,
+            team_count: 2,
+            friendly_fire: false,
+            min_players: 2,
+            round_count: 3,
+            countdown_ticks: 90,
+            round_time_ticks: 3600,
+            round_end_ticks: 150,
+            match_end_ticks: 300,
+            max_rooms: 64,
+        }
+    }
+}
+
+impl WorldConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut config = Self::default();
+
+        if let Ok(map_path) = std::env::var("MAP_PATH") {
+            config.map_path = map_path;
+        }
+        if let Ok(tick_rate) = std::env::var("TICK_RATE") {
+            config.tick_rate = tick_rate.parse()?;
+            anyhow::ensure!(config.tick_rate > 0, "TICK_RATE must be at least 1");
+        }
+        if let Ok(view_radius) = std::env::var("VIEW_RADIUS") {
+            config.view_radius = view_radius.parse()?;
+            anyhow::ensure!(config.view_radius > 0.0, "VIEW_RADIUS must be positive");
+        }
+        if let Ok(bandwidth_per_tick) = std::env::var("BANDWIDTH_PER_TICK") {
+            config.bandwidth_per_tick = bandwidth_per_tick.parse()?;
+            anyhow::ensure!(config.bandwidth_per_tick > 0, "BANDWIDTH_PER_TICK must be at least 1");
+        }
+        if let Ok(self_priority) = std::env::var("SELF_PRIORITY") {
+            config.self_priority = self_priority.parse()?;
+            anyhow::ensure!(config.self_priority > 0.0, "SELF_PRIORITY must be positive");
+        }
+        if let Ok(spawn_policy) = std::env::var("SPAWN_POLICY") {
+            config.spawn_policy = spawn_policy.parse()?;
+        }
+        if let Ok(team_count) = std::env::var("TEAM_COUNT") {
+            config.team_count = team_count.parse()?;
+            anyhow::ensure!(config.team_count > 0, "TEAM_COUNT must be at least 1");
+        }
+        if let Ok(friendly_fire) = std::env::var("FRIENDLY_FIRE") {
+            config.friendly_fire = friendly_fire.parse()?;
+        }
+        if let Ok(min_players) = std::env::var("MIN_PLAYERS") {
+            config.min_players = min_players.parse()?;
+        }
+        if let Ok(round_count) = std::env::var("ROUND_COUNT") {
+            config.round_count = round_count.parse()?;
+            anyhow::ensure!(config.round_count > 0, "ROUND_COUNT must be at least 1");
+        }
+        if let Ok(max_rooms) = std::env::var("MAX_ROOMS") {
+            config.max_rooms = max_rooms.parse()?;
+        }
+        if let Ok(round_time_ticks) = std::env::var("ROUND_TIME_TICKS") {
+            config.round_time_ticks = round_time_ticks.parse()?;
+        }
+
+        Ok(config)
+    }
+}
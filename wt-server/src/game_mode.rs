@@ -0,0 +1,232 @@
+use hecs::{Entity, World};
+use tokio::sync::mpsc::UnboundedSender;
+use uuid::Uuid;
+
+use crate::components::*;
+use crate::config::WorldConfig;
+use crate::index::NetworkIndex;
+use crate::messages::WorldToServer;
+use crate::systems::{current_tick, despawn_networked, respawn_player, Damage};
+
+pub fn spawn_match(world: &mut World, config: &WorldConfig) {
+    world.spawn((Match {
+        phase: MatchPhase::Warmup,
+        phase_end_tick: None,
+        round: 0,
+        round_count: config.round_count,
+        tick_rate: config.tick_rate,
+        round_wins: vec![0; config.team_count as usize],
+        round_eliminations: vec![0; config.team_count as usize],
+        round_winner: None,
+        winner: None,
+        changed: true,
+    },));
+}
+
+pub fn current_phase(world: &World) -> MatchPhase {
+    world.query::<&Match>().iter().next().map_or(MatchPhase::Warmup, |(_, state)| state.phase)
+}
+
+// Warmup -> Countdown -> InRound -> RoundEnd -> Countdown ... -> MatchEnd -> Warmup.
+// Rounds need at least two occupied teams, so a single-team config stays in warmup as free play.
+pub fn update_match(world: &mut World, network_index: &mut NetworkIndex, config: &WorldConfig, to_server: UnboundedSender<WorldToServer>) {
+    let tick_value = current_tick(world);
+    let Some((entity, mut state)) = world.query::<&Match>().iter().next().map(|(entity, state)| (entity, state.clone())) else {
+        return;
+    };
+
+    let (present, standing) = team_counts(world, config.team_count);
+    let players: usize = present.iter().sum();
+    let ready = players >= config.min_players && present.iter().filter(|&&count| count > 0).count() >= 2;
+    let expired = state.phase_end_tick.is_some_and(|end_tick| tick_value >= end_tick);
+
+    match state.phase {
+        MatchPhase::Warmup => {
+            if ready {
+                state.round = 1;
+                state.round_wins.iter_mut().for_each(|wins| *wins = 0);
+                state.winner = None;
+                start_countdown(world, network_index, config, &mut state, tick_value, to_server);
+            }
+        }
+        MatchPhase::Countdown => {
+            if !ready {
+                state.set_phase(MatchPhase::Warmup, None);
+            } else if expired {
+                discard_countdown_input(world);
+                state.set_phase(MatchPhase::InRound, Some(tick_value + config.round_time_ticks));
+            }
+        }
+        MatchPhase::InRound => {
+            let teams_standing = standing.iter().filter(|&&count| count > 0).count();
+            if players == 0 {
+                state.set_phase(MatchPhase::Warmup, None);
+            } else if teams_standing <= 1 || expired {
+                end_round(config, &mut state, &standing, tick_value);
+            }
+        }
+        MatchPhase::RoundEnd => {
+            if expired && !ready {
+                state.set_phase(MatchPhase::Warmup, None);
+            } else if expired {
+                state.round += 1;
+                start_countdown(world, network_index, config, &mut state, tick_value, to_server);
+            }
+        }
+        MatchPhase::MatchEnd => {
+            if expired {
+                state.set_phase(MatchPhase::Warmup, None);
+            }
+        }
+    }
+
+    if let Ok(mut current) = world.get::<&mut Match>(entity) {
+        *current = state;
+    }
+}
+
+// Counts players per team, all of them and those not eliminated
+fn team_counts(world: &World, team_count: u8) -> (Vec<usize>, Vec<usize>) {
+    let mut present = vec![0; team_count as usize];
+    let mut standing = vec![0; team_count as usize];
+    for (_, (_, team, eliminated)) in world.query::<(&Player, &Team, Option<&Eliminated>)>().iter() {
+        let Some(count) = present.get_mut(team.id as usize) else {
+            continue;
+        };
+        *count += 1;
+        if eliminated.is_none() {
+            standing[team.id as usize] += 1;
+        }
+    }
+    (present, standing)
+}
+
+// Every round starts from a clean court: projectiles cleared, everyone back at a spawn point at full health
+fn start_countdown(world: &mut World, network_index: &mut NetworkIndex, config: &WorldConfig, state: &mut Match, tick_value: u64, to_server: UnboundedSender<WorldToServer>) {
+    let projectiles: Vec<Entity> = world.query::<&Projectile>().iter().map(|(entity, _)| entity).collect();
+    for entity in projectiles {
+        despawn_networked(world, network_index, to_server.clone(), entity);
+    }
+
+    let players: Vec<Entity> = world.query::<&Player>().iter().map(|(entity, _)| entity).collect();
+    for entity in players {
+        respawn_player(world, config, entity);
+    }
+
+    state.round_eliminations.iter_mut().for_each(|eliminations| *eliminations = 0);
+    state.round_winner = None;
+    state.set_phase(MatchPhase::Countdown, Some(tick_value + config.countdown_ticks));
+}
+
+// Throws and click paths queued while everyone held still would all fire on the first tick of the
+// round, so the round starts from nothing queued; held directions still apply
+fn discard_countdown_input(world: &mut World) {
+    for (_, (position, target, path, move_type, throw)) in world.query_mut::<(&Position, &mut MoveTarget, &mut Path, &PlayerMove, &mut PlayerThrow)>() {
+        throw.aim = None;
+        path.waypoints.clear();
+        if move_type.move_input_type == MovementType::Target {
+            target.x = position.x;
+            target.y = position.y;
+        }
+    }
+}
+
+// The last team standing takes the round; on time out, most players standing then most eliminations
+fn end_round(config: &WorldConfig, state: &mut Match, standing: &[usize], tick_value: u64) {
+    let teams: Vec<u8> = (0..config.team_count).collect();
+    let leaders = leading_teams(&teams, |team| standing[team as usize]);
+    let leaders = leading_teams(&leaders, |team| state.round_eliminations[team as usize]);
+    state.round_winner = match leaders.as_slice() {
+        [team] => Some(*team),
+        _ => None,
+    };
+    if let Some(team) = state.round_winner {
+        state.round_wins[team as usize] += 1;
+    }
+
+    let wins_needed = state.round_count / 2 + 1;
+    let decided = state.round_wins.iter().any(|&wins| wins >= wins_needed);
+    if decided || state.round >= state.round_count {
+        state.winner = match leading_teams(&teams, |team| state.round_wins[team as usize]).as_slice() {
+            [team] => Some(*team),
+            _ => None,
+        };
+        state.set_phase(MatchPhase::MatchEnd, Some(tick_value + config.match_end_ticks));
+    } else {
+        state.set_phase(MatchPhase::RoundEnd, Some(tick_value + config.round_end_ticks));
+    }
+}
+
+fn leading_teams<K: Ord + Copy>(teams: &[u8], key: impl Fn(u8) -> K) -> Vec<u8> {
+    let Some(best) = teams.iter().map(|&team| key(team)).max() else {
+        return Vec::new();
+    };
+    teams.iter().copied().filter(|&team| key(team) == best).collect()
+}
+
+// Eliminating a teammate scores nothing
+pub fn score_eliminations(world: &mut World, eliminations: &[Damage]) {
+    let scoring: Vec<u8> = eliminations.iter()
+        .filter_map(|damage| {
+            let attacker = *world.get::<&Team>(damage.attacker).ok()?;
+            let target = *world.get::<&Team>(damage.target).ok()?;
+            (attacker != target).then_some(attacker.id)
+        })
+        .collect();
+    if scoring.is_empty() {
+        return;
+    }
+
+    for (_, state) in world.query_mut::<&mut Match>() {
+        if state.phase != MatchPhase::InRound {
+            continue;
+        }
+        for &team in &scoring {
+            if let Some(eliminations) = state.round_eliminations.get_mut(team as usize) {
+                *eliminations += 1;
+                state.changed = true;
+            }
+        }
+    }
+}
+
+// Someone joining mid-round watches until the next round starts
+pub fn admit_player(world: &mut World, entity: Entity) {
+    if matches!(current_phase(world), MatchPhase::Warmup | MatchPhase::Countdown) {
+        return;
+    }
+    if let Ok(mut health) = world.get::<&mut Health>(entity) {
+        health.current = 0;
+    }
+    let _ = world.insert_one(entity, Eliminated { respawn_tick: current_tick(world) });
+}
+
+pub fn send_match(world: &World, connection_id: Uuid, to_server: UnboundedSender<WorldToServer>) {
+    for (_, state) in world.query::<&Match>().iter() {
+        to_server.send(WorldToServer::MatchState {
+            receiver_connection_id: connection_id,
+            state: state.clone(),
+        }).unwrap();
+    }
+}
+
+// Phase changes and score updates go out reliably, once, to everyone; clients count timers down themselves
+pub fn broadcast_match(world: &mut World, to_server: UnboundedSender<WorldToServer>) {
+    let mut changed = None;
+    for (_, state) in world.query_mut::<&mut Match>() {
+        if state.changed {
+            state.changed = false;
+            changed = Some(state.clone());
+        }
+    }
+    let Some(state) = changed else {
+        return;
+    };
+
+    for (_, connection) in world.query::<&Connection>().iter() {
+        to_server.send(WorldToServer::MatchState {
+            receiver_connection_id: connection.connection_id,
+            state: state.clone(),
+        }).unwrap();
+    }
+}
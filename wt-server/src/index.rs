@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use hecs::Entity;
+use uuid::Uuid;
+use crate::components::NetworkId;
+
+#[derive(Debug, Default)]
+pub struct ConnectionIndex {
+    entities: HashMap<Uuid, Entity>,
+}
+
+impl ConnectionIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, connection_id: Uuid, entity: Entity) {
+        self.entities.insert(connection_id, entity);
+    }
+
+    pub fn remove(&mut self, connection_id: Uuid) -> Option<Entity> {
+        self.entities.remove(&connection_id)
+    }
+
+    pub fn get(&self, connection_id: Uuid) -> Option<Entity> {
+        self.entities.get(&connection_id).copied()
+    }
+}
+
+// Network ids are never reused while the server runs, so a late message can't land on a new entity
+#[derive(Debug, Default)]
+pub struct NetworkIndex {
+    entities: HashMap<NetworkId, Entity>,
+    next_id: u32,
+}
+
+impl NetworkIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allocate(&mut self, entity: Entity) -> NetworkId {
+        let network_id = NetworkId(self.next_id);
+        self.next_id += 1;
+        self.entities.insert(network_id, entity);
+        network_id
+    }
+
+    pub fn remove(&mut self, network_id: NetworkId) -> Option<Entity> {
+        self.entities.remove(&network_id)
+    }
+
+    pub fn get(&self, network_id: NetworkId) -> Option<Entity> {
+        self.entities.get(&network_id).copied()
+    }
+}
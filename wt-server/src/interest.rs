@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use hecs::Entity;
+
+type Cell = (i32, i32);
+
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<(Entity, f32, f32)>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for entities in self.cells.values_mut() {
+            entities.clear();
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, x: f32, y: f32) {
+        let cell = self.cell(x, y);
+        self.cells.entry(cell).or_default().push((entity, x, y));
+    }
+
+    pub fn query_radius(&self, x: f32, y: f32, radius: f32) -> Vec<Entity> {
+        let (min_cx, min_cy) = self.cell(x - radius, y - radius);
+        let (max_cx, max_cy) = self.cell(x + radius, y + radius);
+        let radius_squared = radius * radius;
+
+        let mut result = Vec::new();
+        for cx in min_cx..=max_cx {
+            for cy in min_cy..=max_cy {
+                let Some(entities) = self.cells.get(&(cx, cy)) else {
+                    continue;
+                };
+                for &(entity, ex, ey) in entities {
+                    let dx = ex - x;
+                    let dy = ey - y;
+                    if dx * dx + dy * dy <= radius_squared {
+                        result.push(entity);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn cell(&self, x: f32, y: f32) -> Cell {
+        ((x / self.cell_size).floor() as i32, (y / self.cell_size).floor() as i32)
+    }
+}
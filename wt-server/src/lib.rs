@@ -0,0 +1,20 @@
+pub mod config;
+pub mod map;
+pub mod rooms;
+pub mod server;
+pub mod messages;
+pub mod components;
+pub mod broadphase;
+
+mod world;
+mod systems;
+mod network;
+mod scripts;
+mod index;
+mod interest;
+mod tiled;
+mod spawn;
+mod navigation;
+mod replication;
+mod teams;
+mod game_mode;
@@ -1,33 +1,26 @@
+use std::sync::Arc;
 use tokio::sync::mpsc;
-
-mod world;
-mod server;
-mod messages;
-mod components;
-mod systems;
-mod network;
-mod scripts;
-
+use wt_server::config::WorldConfig;
+use wt_server::map::load_map;
+use wt_server::rooms::Rooms;
+use wt_server::server;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> { 
+    let config = WorldConfig::from_env()?;
+    // Loaded once up front so a bad map fails at startup; every room gets its own copy of the world
+    let map = Arc::new(load_map(&config.map_path)?);
 
-    let (server_to_world_tx, server_to_world_rx) = mpsc::unbounded_channel();
     let (world_to_server_tx, world_to_server_rx) = mpsc::unbounded_channel();
+    let rooms = Arc::new(Rooms::new(config, map, world_to_server_tx));
 
     let server_handle = tokio::spawn(server::run_server(
-        server_to_world_tx.clone(),
+        rooms,
         world_to_server_rx,
     ));
 
-    let world_handle = tokio::spawn(world::run_world(
-        server_to_world_rx,
-        world_to_server_tx.clone(),
-    ));
-
-    let (server_result, world_result) = tokio::try_join!(server_handle, world_handle)?;
+    let server_result = server_handle.await?;
     println!("Server finished: {:?}", server_result);
-    println!("World finished: {:?}", world_result);
 
     Ok(())
 }
@@ -0,0 +1,202 @@
+use std::path::Path;
+use anyhow::{Context, Result};
+use hecs::{Entity, World};
+use serde::Deserialize;
+
+use crate::broadphase::CollisionGrid;
+use crate::components::*;
+use crate::navigation::NavGrid;
+use crate::network::build_map_datagram;
+use crate::systems::PLAYER_RADIUS;
+use crate::tiled::import_tiled_map;
+
+const COLLISION_CELL_SIZE: f32 = 64.0;
+const NAV_CELL_SIZE: f32 = 16.0;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Map {
+    pub width: f32,
+    pub height: f32,
+    #[serde(default)]
+    pub collision: Vec<MapPolyline>,
+    #[serde(default)]
+    pub circles: Vec<MapCircle>,
+    #[serde(default)]
+    pub spawn_points: Vec<MapSpawnPoint>,
+    #[serde(default)]
+    pub regions: Vec<MapRegion>,
+    #[serde(default)]
+    pub tilesets: Vec<MapTileset>,
+    #[serde(default)]
+    pub tile_layers: Vec<MapTileLayer>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct MapPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct MapSpawnPoint {
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub team: Option<u8>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MapPolyline {
+    pub points: Vec<MapPoint>,
+    #[serde(default)]
+    pub closed: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct MapCircle {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MapRegion {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    #[serde(default)]
+    pub team: Option<u8>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MapTileset {
+    pub first_gid: u32,
+    pub image: String,
+    pub columns: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MapTileLayer {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub data: Vec<u32>,
+}
+
+impl MapPolyline {
+    // Closed polylines spawn as `CollisionPolygon`s and get their edges in `build_collision_grid`
+    pub fn collision_lines(&self) -> Vec<CollisionLine> {
+        self.points.windows(2)
+            .map(|pair| CollisionLine { x1: pair[0].x, y1: pair[0].y, x2: pair[1].x, y2: pair[1].y })
+            .collect()
+    }
+}
+
+pub fn load_map(path: impl AsRef<Path>) -> Result<Map> {
+    let path = path.as_ref();
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read map {}", path.display()))?;
+
+    let map = if path.extension().is_some_and(|extension| extension == "tmj") {
+        import_tiled_map(&data)
+    } else {
+        serde_json::from_str(&data).map_err(Into::into)
+    };
+    let map = map.with_context(|| format!("failed to parse map {}", path.display()))?;
+
+    // Catch a map clients can't be sent at load, not when the first player joins
+    build_map_datagram(&map).with_context(|| format!("map {} can't be sent to clients", path.display()))?;
+    Ok(map)
+}
+
+pub fn spawn_map(world: &mut World, map: &Map) {
+    world.spawn((
+        MapBounds { width: map.width, height: map.height },
+    ));
+
+    world.spawn((
+        Collision {
+            collision_lines: map.collision.iter()
+                .filter(|polyline| !polyline.closed)
+                .flat_map(MapPolyline::collision_lines)
+                .collect(),
+        },
+    ));
+    for polyline in map.collision.iter().filter(|polyline| polyline.closed) {
+        world.spawn((
+            CollisionPolygon { points: polyline.points.iter().map(|point| (point.x, point.y)).collect() },
+        ));
+    }
+    for circle in &map.circles {
+        world.spawn((
+            CollisionCircle { x: circle.x, y: circle.y, radius: circle.radius },
+        ));
+    }
+    for region in &map.regions {
+        world.spawn((
+            Region { x: region.x, y: region.y, width: region.width, height: region.height, team: region.team },
+        ));
+    }
+    build_collision_grid(world);
+    build_nav_grid(world);
+
+    world.spawn((SpawnRotation { next: 0 },));
+    for spawn_point in &map.spawn_points {
+        world.spawn((
+            SpawnPoint { x: spawn_point.x, y: spawn_point.y, team: spawn_point.team },
+        ));
+    }
+}
+
+pub fn build_collision_grid(world: &mut World) {
+    let previous: Vec<Entity> = world.query::<&CollisionGrid>().iter().map(|(entity, _)| entity).collect();
+    for entity in previous {
+        let _ = world.despawn(entity);
+    }
+
+    let mut shapes: Vec<StaticShape> = world.query::<&Collision>().iter()
+        .flat_map(|(_, collision)| collision.collision_lines.iter().copied().map(StaticShape::Line))
+        .collect();
+
+    // Polygons collide through their edges, so concave outlines work the same as convex ones
+    for (_, polygon) in world.query::<&CollisionPolygon>().iter() {
+        let points = &polygon.points;
+        for i in 0..points.len() {
+            let (x1, y1) = points[i];
+            let (x2, y2) = points[(i + 1) % points.len()];
+            shapes.push(StaticShape::Line(CollisionLine { x1, y1, x2, y2 }));
+        }
+    }
+
+    for (_, circle) in world.query::<&CollisionCircle>().iter() {
+        shapes.push(StaticShape::Circle(*circle));
+    }
+
+    world.spawn((
+        CollisionGrid::new(COLLISION_CELL_SIZE, shapes),
+    ));
+}
+
+pub fn build_nav_grid(world: &mut World) {
+    let previous: Vec<Entity> = world.query::<&NavGrid>().iter().map(|(entity, _)| entity).collect();
+    for entity in previous {
+        let _ = world.despawn(entity);
+    }
+
+    let nav_grid = {
+        let mut bounds_query = world.query::<&MapBounds>();
+        let mut grid_query = world.query::<&CollisionGrid>();
+        let (Some((_, bounds)), Some((_, collision_grid))) = (bounds_query.iter().next(), grid_query.iter().next()) else {
+            return;
+        };
+        let regions: Vec<Region> = world.query::<&Region>().iter().map(|(_, region)| *region).collect();
+        // A little extra clearance keeps paths from hugging walls
+        NavGrid::new(bounds.width, bounds.height, NAV_CELL_SIZE, PLAYER_RADIUS + 2.0, collision_grid, &regions)
+    };
+    world.spawn((nav_grid,));
+}
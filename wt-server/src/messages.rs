@@ -1,14 +1,40 @@
+use std::sync::Arc;
 use uuid::Uuid;
+use crate::components::{Archetype, Match, NetworkId};
+use crate::map::Map;
 
 #[derive(Debug)]
 pub enum ServerToWorld {
-    PlayerJoined { connection_id: Uuid },
+    PlayerJoined { connection_id: Uuid, team: Option<u8> },
+    PlayerLeft { connection_id: Uuid },
     InputClickPressed { connection_id: Uuid, x: f32, y: f32},
+    InputClickHold { connection_id: Uuid, x: f32, y: f32},
+    InputClickReleased { connection_id: Uuid },
+    InputCommand { connection_id: Uuid, command: InputCommand },
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct InputCommand {
+    pub move_x: i8,
+    pub move_y: i8,
+    pub buttons: u8,
+    pub pointer_x: f32,
+    pub pointer_y: f32,
+    // Latest server tick the client had received, i.e. the world it was looking at
+    pub view_tick: u64,
+}
+
+pub const BUTTON_PRIMARY: u8 = 1 << 0;
+pub const BUTTON_THROW: u8 = 1 << 2;
+
 #[derive(Debug)]
 pub enum WorldToServer {
     SendTick { receiver_connection_id: Uuid, tick: u64 },
-    CreatePlayer { receiver_connection_id: Uuid, connection_id: Uuid, x: f32, y: f32},
-    UpdatePlayerPosition { receiver_connection_id: Uuid, connection_id: Uuid, x: f32, y: f32}
-}
\ No newline at end of file
+    SendMap { receiver_connection_id: Uuid, map: Arc<Map> },
+    SpawnEntity { receiver_connection_id: Uuid, network_id: NetworkId, archetype: Archetype, local: bool, count: u8, components: Vec<u8> },
+    DespawnEntity { receiver_connection_id: Uuid, network_id: NetworkId },
+    UpdateEntity { receiver_connection_id: Uuid, network_id: NetworkId, count: u8, components: Vec<u8> },
+    Hit { receiver_connection_id: Uuid, target: NetworkId, attacker: NetworkId, x: f32, y: f32 },
+    Eliminated { receiver_connection_id: Uuid, target: NetworkId, attacker: NetworkId },
+    MatchState { receiver_connection_id: Uuid, state: Match },
+}
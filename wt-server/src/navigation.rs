@@ -0,0 +1,248 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use crate::broadphase::CollisionGrid;
+use crate::components::Region;
+use crate::scripts::{circle_overlaps_region, shape_distance};
+
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (1, 0), (-1, 0), (0, 1), (0, -1),
+    (1, 1), (1, -1), (-1, 1), (-1, -1),
+];
+
+// Walkability grid over the map, where a cell is blocked if a player centred on it would touch geometry.
+// Each team also gets its own copy with the other teams' regions blocked, since players are pushed
+// straight back out of those; `outsider_blocked` covers teams that own no region, and blocks them all.
+#[derive(Debug)]
+pub struct NavGrid {
+    cell_size: f32,
+    width: i32,
+    height: i32,
+    blocked: Vec<bool>,
+    team_blocked: HashMap<u8, Vec<bool>>,
+    outsider_blocked: Vec<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OpenNode {
+    cost: f32,
+    index: usize,
+}
+
+impl Eq for OpenNode {}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the binary heap pops the cheapest node first
+        other.cost.total_cmp(&self.cost).then(other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl NavGrid {
+    pub fn new(width: f32, height: f32, cell_size: f32, clearance: f32, collision_grid: &CollisionGrid, regions: &[Region]) -> Self {
+        let columns = (width / cell_size).ceil().max(1.0) as i32;
+        let rows = (height / cell_size).ceil().max(1.0) as i32;
+
+        let mut blocked = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let x = (column as f32 + 0.5) * cell_size;
+                let y = (row as f32 + 0.5) * cell_size;
+                let outside = x < clearance || y < clearance || x > width - clearance || y > height - clearance;
+                let touching = collision_grid.query_circle(x, y, clearance)
+                    .iter()
+                    .any(|shape| shape_distance(x, y, shape) < clearance);
+                blocked.push(outside || touching);
+            }
+        }
+
+        let region_blocked = |allowed_team: Option<u8>| -> Vec<bool> {
+            blocked.iter().enumerate()
+                .map(|(index, &blocked)| {
+                    let x = ((index as i32 % columns) as f32 + 0.5) * cell_size;
+                    let y = ((index as i32 / columns) as f32 + 0.5) * cell_size;
+                    blocked || regions.iter().any(|region| {
+                        region.team.is_some() && region.team != allowed_team && circle_overlaps_region(x, y, clearance, region)
+                    })
+                })
+                .collect()
+        };
+        let mut team_blocked = HashMap::new();
+        for team in regions.iter().filter_map(|region| region.team) {
+            team_blocked.entry(team).or_insert_with(|| region_blocked(Some(team)));
+        }
+        let outsider_blocked = region_blocked(None);
+
+        Self {
+            cell_size,
+            width: columns,
+            height: rows,
+            blocked,
+            team_blocked,
+            outsider_blocked,
+        }
+    }
+
+    // Plans for a player on `team`, so a goal in another team's region ends at the nearest cell
+    // outside it rather than at a line the player can't cross
+    pub fn find_path(&self, start: (f32, f32), goal: (f32, f32), team: Option<u8>) -> Option<Vec<(f32, f32)>> {
+        let blocked = match team {
+            Some(team) => self.team_blocked.get(&team).unwrap_or(&self.outsider_blocked),
+            None => &self.blocked,
+        };
+
+        let start_cell = self.nearest_open_cell(blocked, self.cell(start.0, start.1))?;
+        let goal_cell = self.nearest_open_cell(blocked, self.cell(goal.0, goal.1))?;
+        let start_index = self.index(start_cell);
+        let goal_index = self.index(goal_cell);
+
+        let mut costs = vec![f32::INFINITY; blocked.len()];
+        let mut came_from = vec![usize::MAX; blocked.len()];
+        let mut open = BinaryHeap::new();
+
+        costs[start_index] = 0.0;
+        open.push(OpenNode { cost: self.heuristic(start_cell, goal_cell), index: start_index });
+
+        while let Some(OpenNode { index, .. }) = open.pop() {
+            if index == goal_index {
+                break;
+            }
+
+            let cell = self.cell_of(index);
+            for (dx, dy) in NEIGHBOURS {
+                let next = (cell.0 + dx, cell.1 + dy);
+                if self.is_blocked(blocked, next) {
+                    continue;
+                }
+                // No cutting corners past blocked cells
+                if dx != 0 && dy != 0 && (self.is_blocked(blocked, (cell.0 + dx, cell.1)) || self.is_blocked(blocked, (cell.0, cell.1 + dy))) {
+                    continue;
+                }
+
+                let step = if dx != 0 && dy != 0 { std::f32::consts::SQRT_2 } else { 1.0 };
+                let next_index = self.index(next);
+                let cost = costs[index] + step;
+                if cost < costs[next_index] {
+                    costs[next_index] = cost;
+                    came_from[next_index] = index;
+                    open.push(OpenNode { cost: cost + self.heuristic(next, goal_cell), index: next_index });
+                }
+            }
+        }
+
+        if costs[goal_index].is_infinite() {
+            return None;
+        }
+
+        let mut cells = vec![goal_index];
+        let mut current = goal_index;
+        while current != start_index {
+            current = came_from[current];
+            cells.push(current);
+        }
+        cells.reverse();
+
+        let mut points: Vec<(f32, f32)> = cells.into_iter().skip(1).map(|index| self.center(self.cell_of(index))).collect();
+        // Finish on the exact click when it is reachable, rather than the middle of its cell
+        if goal_cell == self.cell(goal.0, goal.1) {
+            match points.last_mut() {
+                Some(last) => *last = goal,
+                None => points.push(goal),
+            }
+        }
+
+        Some(self.smooth(blocked, start, points))
+    }
+
+    // Drop waypoints that can be skipped with a straight, unobstructed walk
+    fn smooth(&self, blocked: &[bool], start: (f32, f32), points: Vec<(f32, f32)>) -> Vec<(f32, f32)> {
+        let mut smoothed = Vec::new();
+        let mut from = start;
+        let mut i = 0;
+        while i < points.len() {
+            let mut farthest = i;
+            for j in (i + 1..points.len()).rev() {
+                if self.line_of_sight(blocked, from, points[j]) {
+                    farthest = j;
+                    break;
+                }
+            }
+            from = points[farthest];
+            smoothed.push(from);
+            i = farthest + 1;
+        }
+        smoothed
+    }
+
+    fn line_of_sight(&self, blocked: &[bool], from: (f32, f32), to: (f32, f32)) -> bool {
+        let dx = to.0 - from.0;
+        let dy = to.1 - from.1;
+        let length = (dx * dx + dy * dy).sqrt();
+        let steps = (length / (self.cell_size * 0.25)).ceil().max(1.0) as i32;
+
+        (0..=steps).all(|step| {
+            let t = step as f32 / steps as f32;
+            !self.is_blocked(blocked, self.cell(from.0 + dx * t, from.1 + dy * t))
+        })
+    }
+
+    fn nearest_open_cell(&self, blocked: &[bool], cell: (i32, i32)) -> Option<(i32, i32)> {
+        let cell = (cell.0.clamp(0, self.width - 1), cell.1.clamp(0, self.height - 1));
+        if !self.is_blocked(blocked, cell) {
+            return Some(cell);
+        }
+
+        let mut visited = vec![false; blocked.len()];
+        let mut queue = VecDeque::from([cell]);
+        visited[self.index(cell)] = true;
+        while let Some(current) = queue.pop_front() {
+            if !self.is_blocked(blocked, current) {
+                return Some(current);
+            }
+            for (dx, dy) in NEIGHBOURS {
+                let next = (current.0 + dx, current.1 + dy);
+                if self.in_bounds(next) && !visited[self.index(next)] {
+                    visited[self.index(next)] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    fn heuristic(&self, from: (i32, i32), to: (i32, i32)) -> f32 {
+        let dx = (from.0 - to.0).abs() as f32;
+        let dy = (from.1 - to.1).abs() as f32;
+        dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+    }
+
+    fn cell(&self, x: f32, y: f32) -> (i32, i32) {
+        ((x / self.cell_size).floor() as i32, (y / self.cell_size).floor() as i32)
+    }
+
+    fn center(&self, cell: (i32, i32)) -> (f32, f32) {
+        ((cell.0 as f32 + 0.5) * self.cell_size, (cell.1 as f32 + 0.5) * self.cell_size)
+    }
+
+    fn in_bounds(&self, cell: (i32, i32)) -> bool {
+        cell.0 >= 0 && cell.1 >= 0 && cell.0 < self.width && cell.1 < self.height
+    }
+
+    fn is_blocked(&self, blocked: &[bool], cell: (i32, i32)) -> bool {
+        !self.in_bounds(cell) || blocked[self.index(cell)]
+    }
+
+    fn index(&self, cell: (i32, i32)) -> usize {
+        (cell.1 * self.width + cell.0) as usize
+    }
+
+    fn cell_of(&self, index: usize) -> (i32, i32) {
+        (index as i32 % self.width, index as i32 / self.width)
+    }
+}
@@ -1,13 +1,22 @@
+use anyhow::{anyhow, bail, Result};
 use uuid::Uuid;
-use crate::messages::ServerToWorld;
+use crate::components::{Archetype, Match, NetworkId};
+use crate::map::Map;
+use crate::messages::{InputCommand, ServerToWorld};
+use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::UnboundedSender;
 
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum ServerToClientMessage {
     Tick = 0,
-    CreatePlayer = 1,
-    UpdatePlayerPosition = 2,
+    SpawnEntity = 1,
+    UpdateEntity = 2,
+    DespawnEntity = 3,
+    MapData = 4,
+    Hit = 5,
+    Eliminated = 6,
+    MatchState = 7,
 }
 
 impl ServerToClientMessage {
@@ -27,54 +36,243 @@ pub fn build_tick_datagram(tick: u64) -> Vec<u8> {
     buffer
 }
 
-pub fn build_create_player_datagram(connection_id: Uuid, x: f32, y: f32) -> Vec<u8> {
-    let mut buffer = Vec::with_capacity(1 + 16 + 4 + 4);
+// Fails when a count or size doesn't fit its field, rather than sending a map the client would misread
+pub fn build_map_datagram(map: &Map) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
 
-    buffer.push(ServerToClientMessage::CreatePlayer.to_u8());
+    buffer.push(ServerToClientMessage::MapData.to_u8());
 
-    buffer.extend_from_slice(connection_id.as_bytes());
-    buffer.extend_from_slice(&x.to_le_bytes());
-    buffer.extend_from_slice(&y.to_le_bytes());
+    buffer.extend_from_slice(&map.width.to_le_bytes());
+    buffer.extend_from_slice(&map.height.to_le_bytes());
+
+    buffer.extend_from_slice(&map_u16(map.collision.len(), "polyline count")?);
+    for polyline in &map.collision {
+        buffer.push(polyline.closed as u8);
+        buffer.extend_from_slice(&map_u16(polyline.points.len(), "polyline point count")?);
+        for point in &polyline.points {
+            buffer.extend_from_slice(&point.x.to_le_bytes());
+            buffer.extend_from_slice(&point.y.to_le_bytes());
+        }
+    }
+
+    buffer.extend_from_slice(&map_u16(map.circles.len(), "circle count")?);
+    for circle in &map.circles {
+        buffer.extend_from_slice(&circle.x.to_le_bytes());
+        buffer.extend_from_slice(&circle.y.to_le_bytes());
+        buffer.extend_from_slice(&circle.radius.to_le_bytes());
+    }
+
+    buffer.extend_from_slice(&map_u16(map.spawn_points.len(), "spawn point count")?);
+    for point in &map.spawn_points {
+        buffer.extend_from_slice(&point.x.to_le_bytes());
+        buffer.extend_from_slice(&point.y.to_le_bytes());
+    }
+
+    buffer.extend_from_slice(&map_u16(map.regions.len(), "region count")?);
+    for region in &map.regions {
+        let name = region.name.as_bytes();
+        buffer.push(map_u8(name.len(), "region name length")?);
+        buffer.extend_from_slice(name);
+        buffer.extend_from_slice(&region.x.to_le_bytes());
+        buffer.extend_from_slice(&region.y.to_le_bytes());
+        buffer.extend_from_slice(&region.width.to_le_bytes());
+        buffer.extend_from_slice(&region.height.to_le_bytes());
+    }
+
+    buffer.extend_from_slice(&map_u16(map.tilesets.len(), "tileset count")?);
+    for tileset in &map.tilesets {
+        let image = tileset.image.as_bytes();
+        buffer.extend_from_slice(&tileset.first_gid.to_le_bytes());
+        buffer.extend_from_slice(&map_u16(tileset.columns, "tileset columns")?);
+        buffer.extend_from_slice(&map_u16(tileset.tile_width, "tileset tile width")?);
+        buffer.extend_from_slice(&map_u16(tileset.tile_height, "tileset tile height")?);
+        buffer.extend_from_slice(&map_u16(image.len(), "tileset image path length")?);
+        buffer.extend_from_slice(image);
+    }
+
+    buffer.extend_from_slice(&map_u16(map.tile_layers.len(), "tile layer count")?);
+    for layer in &map.tile_layers {
+        // The client reads exactly width * height tiles per layer
+        if layer.data.len() != layer.width as usize * layer.height as usize {
+            bail!("tile layer has {} tiles, expected {} x {}", layer.data.len(), layer.width, layer.height);
+        }
+        buffer.extend_from_slice(&map_u16(layer.width, "tile layer width")?);
+        buffer.extend_from_slice(&map_u16(layer.height, "tile layer height")?);
+        buffer.extend_from_slice(&map_u16(layer.tile_width, "tile layer tile width")?);
+        buffer.extend_from_slice(&map_u16(layer.tile_height, "tile layer tile height")?);
+        for gid in &layer.data {
+            buffer.extend_from_slice(&gid.to_le_bytes());
+        }
+    }
+
+    Ok(buffer)
+}
+
+fn map_u8(value: usize, field: &str) -> Result<u8> {
+    u8::try_from(value).map_err(|_| anyhow!("map {} does not fit in a u8", field))
+}
+
+fn map_u16(value: impl TryInto<u16>, field: &str) -> Result<[u8; 2]> {
+    value.try_into()
+        .map(u16::to_le_bytes)
+        .map_err(|_| anyhow!("map {} does not fit in a u16", field))
+}
+
+const SPAWN_FLAG_LOCAL: u8 = 1 << 0;
+
+// `local` marks the receiver's own player so the client knows which entity to follow.
+// The initial component records follow so the entity never appears with default state.
+pub fn build_spawn_entity_datagram(network_id: NetworkId, archetype: Archetype, local: bool, count: u8, components: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(1 + 4 + 1 + 1 + 1 + components.len());
+
+    buffer.push(ServerToClientMessage::SpawnEntity.to_u8());
+
+    buffer.extend_from_slice(&network_id.0.to_le_bytes());
+    buffer.push(archetype.to_u8());
+    buffer.push(if local { SPAWN_FLAG_LOCAL } else { 0 });
+    buffer.push(count);
+    buffer.extend_from_slice(components);
+
+    buffer
+}
+
+pub fn build_despawn_entity_datagram(network_id: NetworkId) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(1 + 4);
+
+    buffer.push(ServerToClientMessage::DespawnEntity.to_u8());
+
+    buffer.extend_from_slice(&network_id.0.to_le_bytes());
+
+    buffer
+}
+
+pub fn update_entity_size(components_len: usize) -> usize {
+    1 + 4 + 1 + components_len
+}
+
+// `components` is the already-encoded `id, length, payload` records from the replication registry
+pub fn build_update_entity_datagram(network_id: NetworkId, count: u8, components: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(update_entity_size(components.len()));
+
+    buffer.push(ServerToClientMessage::UpdateEntity.to_u8());
+
+    buffer.extend_from_slice(&network_id.0.to_le_bytes());
+    buffer.push(count);
+    buffer.extend_from_slice(components);
 
     buffer
 }
 
-pub fn build_update_player_position_datagram(connection_id: Uuid, x: f32, y: f32) -> Vec<u8> {
-    let mut buffer = Vec::with_capacity(1 + 16 + 4 + 4);
+pub fn build_hit_datagram(target: NetworkId, attacker: NetworkId, x: f32, y: f32) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(1 + 4 + 4 + 4 + 4);
 
-    buffer.push(ServerToClientMessage::UpdatePlayerPosition.to_u8());
+    buffer.push(ServerToClientMessage::Hit.to_u8());
 
-    buffer.extend_from_slice(connection_id.as_bytes());
+    buffer.extend_from_slice(&target.0.to_le_bytes());
+    buffer.extend_from_slice(&attacker.0.to_le_bytes());
     buffer.extend_from_slice(&x.to_le_bytes());
     buffer.extend_from_slice(&y.to_le_bytes());
 
     buffer
 }
 
+pub fn build_eliminated_datagram(target: NetworkId, attacker: NetworkId) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(1 + 4 + 4);
+
+    buffer.push(ServerToClientMessage::Eliminated.to_u8());
+
+    buffer.extend_from_slice(&target.0.to_le_bytes());
+    buffer.extend_from_slice(&attacker.0.to_le_bytes());
+
+    buffer
+}
+
+// Team ids that aren't set go out as 255; a phase end tick of 0 means the phase isn't timed
+pub fn build_match_state_datagram(state: &Match) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(1 + 1 + 1 + 1 + 2 + 8 + 1 + 1 + 1 + state.round_wins.len() * 3);
+
+    buffer.push(ServerToClientMessage::MatchState.to_u8());
+
+    buffer.push(state.phase.to_u8());
+    buffer.push(state.round);
+    buffer.push(state.round_count);
+    buffer.extend_from_slice(&state.tick_rate.to_le_bytes());
+    buffer.extend_from_slice(&state.phase_end_tick.unwrap_or(0).to_le_bytes());
+    buffer.push(state.round_winner.unwrap_or(u8::MAX));
+    buffer.push(state.winner.unwrap_or(u8::MAX));
+
+    buffer.push(state.round_wins.len() as u8);
+    for (wins, eliminations) in state.round_wins.iter().zip(&state.round_eliminations) {
+        buffer.push(*wins);
+        buffer.extend_from_slice(&eliminations.to_le_bytes());
+    }
+
+    buffer
+}
+
 
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub enum ClientToServerMessage {
     InputClickPressed = 0,
+    InputClickHold = 1,
+    InputClickReleased = 2,
+    InputCommand = 3,
 }
 
 impl ClientToServerMessage {
     pub fn from_u8(value: u8) -> Option<Self> {
         match value {
             0 => Some(ClientToServerMessage::InputClickPressed),
+            1 => Some(ClientToServerMessage::InputClickHold),
+            2 => Some(ClientToServerMessage::InputClickReleased),
+            3 => Some(ClientToServerMessage::InputCommand),
             _ => None,
         }
     }
 }
 
-pub fn decode_input_click_pressed(connection_id: Uuid, to_world: UnboundedSender<ServerToWorld>, data: &[u8]) {
+pub fn decode_input_click_pressed(connection_id: Uuid, to_world: UnboundedSender<ServerToWorld>, data: &[u8]) -> Result<(), SendError<ServerToWorld>> {
     if data.len() < 1 + 4 + 4 {
-        return;
+        return Ok(());
     }
 
     let x = f32::from_le_bytes(data[1..5].try_into().unwrap());
     let y = f32::from_le_bytes(data[5..9].try_into().unwrap());
     
     println!("Player {} Clicked at: {} {})", connection_id, x, y);
-    to_world.send(ServerToWorld::InputClickPressed { connection_id: connection_id, x: x, y: y }).unwrap();
+    to_world.send(ServerToWorld::InputClickPressed { connection_id, x, y })
+}
+
+pub fn decode_input_click_hold(connection_id: Uuid, to_world: UnboundedSender<ServerToWorld>, data: &[u8]) -> Result<(), SendError<ServerToWorld>> {
+    if data.len() < 1 + 4 + 4 {
+        return Ok(());
+    }
+
+    let x = f32::from_le_bytes(data[1..5].try_into().unwrap());
+    let y = f32::from_le_bytes(data[5..9].try_into().unwrap());
+
+    to_world.send(ServerToWorld::InputClickHold { connection_id, x, y })
+}
+
+pub fn decode_input_click_released(connection_id: Uuid, to_world: UnboundedSender<ServerToWorld>, _data: &[u8]) -> Result<(), SendError<ServerToWorld>> {
+    to_world.send(ServerToWorld::InputClickReleased { connection_id })
+}
+
+pub fn decode_input_command(connection_id: Uuid, to_world: UnboundedSender<ServerToWorld>, data: &[u8]) -> Result<(), SendError<ServerToWorld>> {
+    if data.len() < 1 + 1 + 1 + 1 + 4 + 4 + 8 {
+        return Ok(());
+    }
+
+    let command = InputCommand {
+        move_x: data[1] as i8,
+        move_y: data[2] as i8,
+        buttons: data[3],
+        pointer_x: f32::from_le_bytes(data[4..8].try_into().unwrap()),
+        pointer_y: f32::from_le_bytes(data[8..12].try_into().unwrap()),
+        view_tick: u64::from_le_bytes(data[12..20].try_into().unwrap()),
+    };
+
+    to_world.send(ServerToWorld::InputCommand { connection_id, command })
 }
\ No newline at end of file
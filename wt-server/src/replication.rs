@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use hecs::{Component, Entity, World};
+use crate::components::*;
+
+// Components opt into replication by implementing this and being registered in `default_registry`.
+// `ID` must match the client's registration for the same component.
+pub trait Replicate: Component {
+    const ID: u8;
+    // Only checked for changes on ticks divisible by this; full refreshes ignore it
+    const EVERY_TICKS: u64 = 1;
+
+    fn encode(&self, buffer: &mut Vec<u8>);
+}
+
+impl Replicate for Position {
+    const ID: u8 = 0;
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.x.to_le_bytes());
+        buffer.extend_from_slice(&self.y.to_le_bytes());
+    }
+}
+
+impl Replicate for State {
+    const ID: u8 = 1;
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.state.to_u8());
+    }
+}
+
+impl Replicate for Facing {
+    const ID: u8 = 2;
+    const EVERY_TICKS: u64 = 2;
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.angle.to_le_bytes());
+    }
+}
+
+impl Replicate for Velocity {
+    const ID: u8 = 3;
+    const EVERY_TICKS: u64 = 2;
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.x.to_le_bytes());
+        buffer.extend_from_slice(&self.y.to_le_bytes());
+    }
+}
+
+impl Replicate for Health {
+    const ID: u8 = 4;
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.current.to_le_bytes());
+        buffer.extend_from_slice(&self.max.to_le_bytes());
+    }
+}
+
+impl Replicate for Team {
+    const ID: u8 = 5;
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.id);
+    }
+}
+
+// Only the radius is replicated; it's fixed at spawn, so it rides in the spawn snapshot and the
+// diff never finds it changed afterwards
+impl Replicate for Projectile {
+    const ID: u8 = 6;
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.radius.to_le_bytes());
+    }
+}
+
+// The encoded bytes of each component as last sent to one receiver
+#[derive(Debug, Clone, Default)]
+pub struct SentComponents {
+    components: HashMap<u8, Vec<u8>>,
+    full_tick: u64,
+}
+
+// Component records ready to go on the wire, each `id, length, payload`
+#[derive(Debug)]
+pub struct ComponentDelta {
+    pub count: u8,
+    pub bytes: Vec<u8>,
+    pub sent: SentComponents,
+}
+
+struct Registration {
+    id: u8,
+    every_ticks: u64,
+    encode: fn(&World, Entity, &mut Vec<u8>) -> bool,
+}
+
+#[derive(Default)]
+pub struct ReplicationRegistry {
+    registrations: Vec<Registration>,
+}
+
+impl ReplicationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: Replicate>(&mut self) -> &mut Self {
+        assert!(
+            self.registrations.iter().all(|registration| registration.id != T::ID),
+            "replicated component id {} registered twice",
+            T::ID
+        );
+        self.registrations.push(Registration {
+            id: T::ID,
+            every_ticks: T::EVERY_TICKS.max(1),
+            encode: encode_component::<T>,
+        });
+        self
+    }
+
+    // Compares `entity` against what the receiver was last sent. Datagrams can be lost, so every
+    // `refresh_ticks` all components are resent regardless and the receiver converges again.
+    pub fn diff(
+        &self,
+        world: &World,
+        entity: Entity,
+        previous: Option<&SentComponents>,
+        tick: u64,
+        refresh_ticks: u64,
+    ) -> Option<ComponentDelta> {
+        let full = previous.is_none_or(|previous| tick.saturating_sub(previous.full_tick) >= refresh_ticks);
+        let previous = previous.filter(|_| !full);
+
+        let mut changes = Vec::new();
+        let mut bytes = Vec::new();
+        let mut payload = Vec::new();
+        for registration in &self.registrations {
+            if !full && !tick.is_multiple_of(registration.every_ticks) {
+                continue;
+            }
+
+            payload.clear();
+            if !(registration.encode)(world, entity, &mut payload) {
+                continue;
+            }
+            if previous.and_then(|previous| previous.components.get(&registration.id)) == Some(&payload) {
+                continue;
+            }
+
+            let length = u8::try_from(payload.len()).expect("replicated component larger than 255 bytes");
+            bytes.push(registration.id);
+            bytes.push(length);
+            bytes.extend_from_slice(&payload);
+            changes.push((registration.id, std::mem::take(&mut payload)));
+        }
+
+        if changes.is_empty() {
+            return None;
+        }
+
+        // Most calls find nothing changed, so the receiver's copy is only rebuilt once something did
+        let count = changes.len() as u8;
+        let mut sent = match previous {
+            Some(previous) => previous.clone(),
+            None => SentComponents { components: HashMap::new(), full_tick: tick },
+        };
+        sent.components.extend(changes);
+        Some(ComponentDelta { count, bytes, sent })
+    }
+}
+
+fn encode_component<T: Replicate>(world: &World, entity: Entity, buffer: &mut Vec<u8>) -> bool {
+    match world.get::<&T>(entity) {
+        Ok(component) => {
+            component.encode(buffer);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+pub fn default_registry() -> ReplicationRegistry {
+    let mut registry = ReplicationRegistry::new();
+    registry
+        .register::<Position>()
+        .register::<State>()
+        .register::<Facing>()
+        .register::<Velocity>()
+        .register::<Health>()
+        .register::<Team>()
+        .register::<Projectile>();
+    registry
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use dashmap::DashMap;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tracing::{error, info};
+
+use crate::config::WorldConfig;
+use crate::map::Map;
+use crate::messages::{ServerToWorld, WorldToServer};
+use crate::world::run_world;
+
+pub const DEFAULT_ROOM: &str = "default";
+const MAX_ROOM_ID_LENGTH: usize = 32;
+
+struct Room {
+    to_world: UnboundedSender<ServerToWorld>,
+    players: usize,
+}
+
+// Each room runs its own `run_world` task with its own world, map and tick loop. A room opens when
+// its first player joins and closes when its last player leaves: dropping the room's sender ends
+// its world loop once the queued messages are handled. All rooms share the one channel back to
+// the server, since world messages are already addressed by connection id.
+pub struct Rooms {
+    rooms: Arc<DashMap<String, Room>>,
+    config: WorldConfig,
+    map: Arc<Map>,
+    to_server: UnboundedSender<WorldToServer>,
+}
+
+impl Rooms {
+    pub fn new(config: WorldConfig, map: Arc<Map>, to_server: UnboundedSender<WorldToServer>) -> Self {
+        Self { rooms: Arc::new(DashMap::new()), config, map, to_server }
+    }
+
+    // Returns the room's channel, or `None` when opening another room would go over `max_rooms`.
+    // The limit is checked before the room is inserted, so concurrent joins can overshoot it slightly.
+    pub fn join(&self, room_id: &str) -> Option<UnboundedSender<ServerToWorld>> {
+        // A room whose world stopped on an error is replaced rather than joined
+        self.rooms.remove_if(room_id, |_, room| room.to_world.is_closed());
+
+        if !self.rooms.contains_key(room_id) && self.rooms.len() >= self.config.max_rooms {
+            return None;
+        }
+
+        let mut room = self.rooms.entry(room_id.to_string()).or_insert_with(|| self.open(room_id));
+        room.players += 1;
+        Some(room.to_world.clone())
+    }
+
+    pub fn leave(&self, room_id: &str) {
+        if let Some(mut room) = self.rooms.get_mut(room_id) {
+            room.players = room.players.saturating_sub(1);
+        }
+        if self.rooms.remove_if(room_id, |_, room| room.players == 0).is_some() {
+            info!("Room '{}' closed", room_id);
+        }
+    }
+
+    fn open(&self, room_id: &str) -> Room {
+        let (to_world, from_server) = mpsc::unbounded_channel();
+        info!("Room '{}' opened", room_id);
+
+        let rooms = self.rooms.clone();
+        let room_id = room_id.to_string();
+        let world = run_world(from_server, self.to_server.clone(), self.config.clone(), self.map.clone());
+        tokio::spawn(async move {
+            if let Err(err) = world.await {
+                error!("Room '{}' stopped: {:?}", room_id, err);
+            }
+            rooms.remove_if(&room_id, |_, room| room.to_world.is_closed());
+        });
+
+        Room { to_world, players: 0 }
+    }
+}
+
+// Rooms are picked by the session path, `/<room id>`; the bare root joins the default room
+pub fn room_id(path: &str) -> Option<String> {
+    let path = path.split_once('?').map_or(path, |(path, _)| path);
+    let room_id = path.trim_matches('/');
+    if room_id.is_empty() {
+        return Some(DEFAULT_ROOM.to_string());
+    }
+
+    let valid = room_id.len() <= MAX_ROOM_ID_LENGTH
+        && room_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| room_id.to_string())
+}
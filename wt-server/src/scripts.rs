@@ -1,93 +1,381 @@
 use crate::components::*;
 
+// Contacts stop just short of the surface so the next sweep doesn't start overlapping
+pub const CONTACT_SKIN: f32 = 0.01;
+
 pub fn collision_slide_velocity(
     position: &Position,
     velocity: &Velocity,
     player_collision: &PlayerCollision,
-    collision: &Collision,
+    shapes: &[&StaticShape],
     iterations: u8,
 ) -> (f32, f32) {
-    let mut result_velocity_x = velocity.x;
-    let mut result_velocity_y = velocity.y;
+    let radius = player_collision.radius;
+    let start_x = position.x + player_collision.offset_x;
+    let start_y = position.y + player_collision.offset_y;
 
-    let mut collided = false;
+    let mut x = start_x;
+    let mut y = start_y;
+    let mut remaining_x = velocity.x;
+    let mut remaining_y = velocity.y;
 
     for _ in 0..iterations {
-        let predicted_x = (position.x + player_collision.offset_x) + result_velocity_x;
-        let predicted_y = (position.y + player_collision.offset_y) + result_velocity_y;
-
-        collided = false;
-        
-        for line in &collision.collision_lines {
-            let x1 = line.x1;
-            let y1 = line.y1;
-            let x2 = line.x2;
-            let y2 = line.y2;
-
-            let min_x = x1.min(x2) - player_collision.radius;
-            let max_x = x1.max(x2) + player_collision.radius;
-            let min_y = y1.min(y2) - player_collision.radius;
-            let max_y = y1.max(y2) + player_collision.radius;
-
-            if predicted_x < min_x || predicted_x > max_x || predicted_y < min_y || predicted_y > max_y {
+        // Push out of anything already overlapping before sweeping
+        for shape in shapes {
+            let Some((distance, nx, ny)) = shape_contact(x, y, shape) else {
                 continue;
-            }
+            };
 
-            
+            if distance < radius {
+                let penetration = radius - distance;
+                x += nx * penetration;
+                y += ny * penetration;
 
-            let dx = x2 - x1;
-            let dy = y2 - y1;
-            let line_length_squared = dx * dx + dy * dy;
-            if line_length_squared == 0.0 {
-                continue;
+                let dot = remaining_x * nx + remaining_y * ny;
+                if dot < 0.0 {
+                    remaining_x -= nx * dot;
+                    remaining_y -= ny * dot;
+                }
             }
+        }
+
+        let length_squared = remaining_x * remaining_x + remaining_y * remaining_y;
+        if length_squared == 0.0 {
+            break;
+        }
+
+        let Some((t, nx, ny)) = earliest_sweep(x, y, remaining_x, remaining_y, radius, shapes) else {
+            x += remaining_x;
+            y += remaining_y;
+            break;
+        };
 
-            
+        let travel = (t - CONTACT_SKIN / length_squared.sqrt()).max(0.0);
+        x += remaining_x * travel;
+        y += remaining_y * travel;
 
-            let t = (((predicted_x - x1) * dx + (predicted_y - y1) * dy) / line_length_squared)
-                .clamp(0.0, 1.0);
+        // Slide along the surface with whatever motion is left
+        remaining_x *= 1.0 - travel;
+        remaining_y *= 1.0 - travel;
+        let dot = remaining_x * nx + remaining_y * ny;
+        if dot < 0.0 {
+            remaining_x -= nx * dot;
+            remaining_y -= ny * dot;
+        }
+    }
 
-            let closest_x = x1 + t * dx;
-            let closest_y = y1 + t * dy;
+    let mut result_velocity_x = x - start_x;
+    let mut result_velocity_y = y - start_y;
 
-            let distance_x = predicted_x - closest_x;
-            let distance_y = predicted_y - closest_y;
-            let distance_squared = distance_x * distance_x + distance_y * distance_y;
+    if result_velocity_x.abs() <= 0.1 && result_velocity_y.abs() <= 0.1 {
+        result_velocity_x = 0.0;
+        result_velocity_y = 0.0;
+    }
 
-            if distance_squared < player_collision.radius * player_collision.radius {
-                collided = true;
+    (result_velocity_x, result_velocity_y)
+}
 
-                let distance = distance_squared.sqrt();
-                let penetration = player_collision.radius - distance;
+// Earliest time of impact of a moving circle against any of `shapes`, with the contact normal
+pub fn earliest_sweep(x: f32, y: f32, dx: f32, dy: f32, radius: f32, shapes: &[&StaticShape]) -> Option<(f32, f32, f32)> {
+    let mut earliest: Option<(f32, f32, f32)> = None;
+    for shape in shapes {
+        if let Some(hit) = sweep_circle_shape(x, y, dx, dy, radius, shape)
+            && earliest.is_none_or(|(t, _, _)| hit.0 < t)
+        {
+            earliest = Some(hit);
+        }
+    }
+    earliest
+}
 
-                let (nx, ny) = if distance != 0.0 {
-                    (distance_x / distance, distance_y / distance)
-                } else {
-                    (0.0, 0.0)
-                };
+pub fn closest_point_on_segment(x: f32, y: f32, line: &CollisionLine) -> (f32, f32) {
+    let dx = line.x2 - line.x1;
+    let dy = line.y2 - line.y1;
+    let line_length_squared = dx * dx + dy * dy;
 
-                result_velocity_x += nx * penetration;
-                result_velocity_y += ny * penetration;
+    let t = if line_length_squared == 0.0 {
+        0.0
+    } else {
+        (((x - line.x1) * dx + (y - line.y1) * dy) / line_length_squared).clamp(0.0, 1.0)
+    };
 
-                let dot = result_velocity_x * nx + result_velocity_y * ny;
-                if dot < 0.0 {
-                    result_velocity_x -= nx * dot;
-                    result_velocity_y -= ny * dot;
-                }
+    (line.x1 + t * dx, line.y1 + t * dy)
+}
+
+// Distance from a point to the outside of a shape, with the outward normal
+pub fn shape_contact(x: f32, y: f32, shape: &StaticShape) -> Option<(f32, f32, f32)> {
+    let (distance_x, distance_y, offset) = match shape {
+        StaticShape::Line(line) => {
+            let (closest_x, closest_y) = closest_point_on_segment(x, y, line);
+            (x - closest_x, y - closest_y, 0.0)
+        }
+        StaticShape::Circle(circle) => (x - circle.x, y - circle.y, circle.radius),
+    };
+
+    let length = (distance_x * distance_x + distance_y * distance_y).sqrt();
+    if length == 0.0 {
+        return None;
+    }
+
+    Some((length - offset, distance_x / length, distance_y / length))
+}
+
+pub fn shape_distance(x: f32, y: f32, shape: &StaticShape) -> f32 {
+    match shape_contact(x, y, shape) {
+        Some((distance, _, _)) => distance,
+        None => match shape {
+            StaticShape::Line(_) => 0.0,
+            StaticShape::Circle(circle) => -circle.radius,
+        },
+    }
+}
+
+pub fn sweep_circle_shape(x: f32, y: f32, dx: f32, dy: f32, radius: f32, shape: &StaticShape) -> Option<(f32, f32, f32)> {
+    match shape {
+        StaticShape::Line(line) => sweep_circle_segment(x, y, dx, dy, radius, line),
+        StaticShape::Circle(circle) => sweep_circle_point(x, y, dx, dy, radius + circle.radius, circle.x, circle.y),
+    }
+}
+
+// Time of impact in [0, 1] of a circle moving by (dx, dy) against a segment, with the contact normal
+pub fn sweep_circle_segment(x: f32, y: f32, dx: f32, dy: f32, radius: f32, line: &CollisionLine) -> Option<(f32, f32, f32)> {
+    let min_x = line.x1.min(line.x2) - radius;
+    let max_x = line.x1.max(line.x2) + radius;
+    let min_y = line.y1.min(line.y2) - radius;
+    let max_y = line.y1.max(line.y2) + radius;
+    if x.max(x + dx) < min_x || x.min(x + dx) > max_x || y.max(y + dy) < min_y || y.min(y + dy) > max_y {
+        return None;
+    }
+
+    let mut earliest: Option<(f32, f32, f32)> = None;
 
-                break; // Check again from start
+    // Flat sides of the segment
+    let ex = line.x2 - line.x1;
+    let ey = line.y2 - line.y1;
+    let line_length_squared = ex * ex + ey * ey;
+    if line_length_squared > 0.0 {
+        let line_length = line_length_squared.sqrt();
+        let (line_nx, line_ny) = (-ey / line_length, ex / line_length);
+
+        let start_distance = (x - line.x1) * line_nx + (y - line.y1) * line_ny;
+        let side = if start_distance >= 0.0 { 1.0 } else { -1.0 };
+        let end_distance = (start_distance + dx * line_nx + dy * line_ny) * side;
+        let start_distance = start_distance * side;
+
+        if start_distance >= radius && end_distance < radius {
+            let t = (start_distance - radius) / (start_distance - end_distance);
+            let hit_x = x + dx * t;
+            let hit_y = y + dy * t;
+            let u = ((hit_x - line.x1) * ex + (hit_y - line.y1) * ey) / line_length_squared;
+            if (0.0..=1.0).contains(&u) {
+                earliest = Some((t, line_nx * side, line_ny * side));
             }
         }
+    }
 
-        if !collided {
-            break;
+    // Rounded ends of the segment
+    for (corner_x, corner_y) in [(line.x1, line.y1), (line.x2, line.y2)] {
+        if let Some(hit) = sweep_circle_point(x, y, dx, dy, radius, corner_x, corner_y)
+            && earliest.is_none_or(|(t, _, _)| hit.0 < t)
+        {
+            earliest = Some(hit);
         }
     }
 
-    if collided || (result_velocity_x.abs() <= 0.1 && result_velocity_y.abs() <= 0.1) {
-        result_velocity_x = 0.0;
-        result_velocity_y = 0.0;
+    earliest
+}
+
+// Time of impact of a moving circle against a fixed point, which also covers circle against circle
+pub fn sweep_circle_point(x: f32, y: f32, dx: f32, dy: f32, radius: f32, point_x: f32, point_y: f32) -> Option<(f32, f32, f32)> {
+    let a = dx * dx + dy * dy;
+    if a == 0.0 {
+        return None;
     }
 
-    (result_velocity_x, result_velocity_y)
-}
\ No newline at end of file
+    let fx = x - point_x;
+    let fy = y - point_y;
+    let b = 2.0 * (fx * dx + fy * dy);
+    let c = fx * fx + fy * fy - radius * radius;
+    if c < 0.0 {
+        return None;
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let t = (-b - discriminant.sqrt()) / (2.0 * a);
+    if !(0.0..=1.0).contains(&t) {
+        return None;
+    }
+
+    let hit_x = fx + dx * t;
+    let hit_y = fy + dy * t;
+    let hit_length = (hit_x * hit_x + hit_y * hit_y).sqrt();
+    if hit_length == 0.0 {
+        return None;
+    }
+
+    Some((t, hit_x / hit_length, hit_y / hit_length))
+}
+
+pub fn replication_priority(
+    distance: f32,
+    view_radius: f32,
+    ticks_since_update: u64,
+    relevance: f32,
+) -> f32 {
+    // Closer entities matter more, and anything deferred grows in priority until it gets sent
+    let proximity = 1.0 - (distance / view_radius).clamp(0.0, 1.0) * 0.9;
+    relevance * proximity * ticks_since_update as f32
+}
+
+pub fn circle_overlaps_region(x: f32, y: f32, radius: f32, region: &Region) -> bool {
+    let closest_x = x.clamp(region.x, region.x + region.width);
+    let closest_y = y.clamp(region.y, region.y + region.height);
+    let (dx, dy) = (x - closest_x, y - closest_y);
+    dx * dx + dy * dy < radius * radius
+}
+
+// Pushes a circle out of every region reserved for another team, along the shortest way out that
+// stays inside the map
+pub fn keep_out_of_regions(x: f32, y: f32, radius: f32, team: u8, regions: &[Region], bounds: Option<(f32, f32)>) -> (f32, f32) {
+    let (mut x, mut y) = (x, y);
+    for region in regions {
+        if region.team.is_none_or(|region_team| region_team == team) {
+            continue;
+        }
+
+        if !circle_overlaps_region(x, y, radius, region) {
+            continue;
+        }
+
+        let exits = [
+            (region.x - radius, y),
+            (region.x + region.width + radius, y),
+            (x, region.y - radius),
+            (x, region.y + region.height + radius),
+        ];
+        let inside_map = |&(exit_x, exit_y): &(f32, f32)| bounds.is_none_or(|(width, height)| {
+            exit_x >= radius && exit_x <= width - radius && exit_y >= radius && exit_y <= height - radius
+        });
+        if let Some(&(exit_x, exit_y)) = exits.iter()
+            .filter(|exit| inside_map(exit))
+            .min_by(|a, b| ((a.0 - x).abs() + (a.1 - y).abs()).total_cmp(&((b.0 - x).abs() + (b.1 - y).abs())))
+        {
+            x = exit_x;
+            y = exit_y;
+        }
+    }
+    (x, y)
+}
+
+pub fn circle_separation(x1: f32, y1: f32, radius1: f32, x2: f32, y2: f32, radius2: f32) -> (f32, f32, f32) {
+    let dx = x2 - x1;
+    let dy = y2 - y1;
+    let distance = (dx * dx + dy * dy).sqrt();
+    let penetration = radius1 + radius2 - distance;
+
+    // Perfectly stacked circles get a fixed axis so callers stay deterministic
+    if distance == 0.0 {
+        return (1.0, 0.0, penetration);
+    }
+
+    (dx / distance, dy / distance, penetration)
+}
+
+#[cfg(test)]
+mod tests {
+    use hecs::World;
+
+    use super::*;
+    use crate::broadphase::CollisionGrid;
+    use crate::map::{load_map, spawn_map};
+    use crate::systems::{PLAYER_RADIUS, SLIDE_ITERATIONS};
+
+    // Far more than the player radius per tick, so a plain end-of-move overlap test would tunnel
+    const FAST: f32 = PLAYER_RADIUS * 12.0;
+    const TOLERANCE: f32 = 0.05;
+
+    fn default_map_world() -> World {
+        let mut world = World::new();
+        let map = load_map("maps/default.json").expect("default map loads");
+        spawn_map(&mut world, &map);
+        world
+    }
+
+    // Mirrors `handle_state`: query the grid around the player, then slide
+    fn slide(world: &World, x: f32, y: f32, vx: f32, vy: f32) -> (f32, f32) {
+        let mut grid_query = world.query::<&CollisionGrid>();
+        let (_, grid) = grid_query.iter().next().expect("collision grid");
+        let position = Position { x, y };
+        let velocity = Velocity { x: vx, y: vy };
+        let collision = PlayerCollision { radius: PLAYER_RADIUS, offset_x: 0.0, offset_y: 0.0 };
+
+        let speed = (vx * vx + vy * vy).sqrt();
+        let reach = PLAYER_RADIUS * (SLIDE_ITERATIONS as f32 + 1.0) + speed;
+        let shapes = grid.query_circle(x, y, reach);
+        let (dx, dy) = collision_slide_velocity(&position, &velocity, &collision, &shapes, SLIDE_ITERATIONS);
+        (x + dx, y + dy)
+    }
+
+    fn assert_clear_of_walls(world: &World, x: f32, y: f32) {
+        let mut grid_query = world.query::<&CollisionGrid>();
+        let (_, grid) = grid_query.iter().next().expect("collision grid");
+        for shape in grid.query_circle(x, y, PLAYER_RADIUS * 2.0) {
+            let distance = shape_distance(x, y, shape);
+            assert!(distance >= PLAYER_RADIUS - TOLERANCE, "player at ({x}, {y}) overlaps {shape:?} by {}", PLAYER_RADIUS - distance);
+        }
+    }
+
+    #[test]
+    fn fast_move_stops_at_top_wall() {
+        let world = default_map_world();
+        let (x, y) = slide(&world, 256.0, 60.0, 0.0, FAST);
+
+        assert!((x - 256.0).abs() < TOLERANCE);
+        assert!((128.0 - PLAYER_RADIUS - 1.0..=128.0 - PLAYER_RADIUS + TOLERANCE).contains(&y), "stopped at y {y}");
+        assert_clear_of_walls(&world, x, y);
+    }
+
+    #[test]
+    fn fast_move_stops_at_side_wall() {
+        let world = default_map_world();
+        let (x, y) = slide(&world, 460.0, 230.0, -FAST, 0.0);
+
+        assert!((320.0 + PLAYER_RADIUS - TOLERANCE..320.0 + PLAYER_RADIUS + 1.0).contains(&x), "stopped at x {x}");
+        assert!((y - 230.0).abs() < TOLERANCE);
+        assert_clear_of_walls(&world, x, y);
+    }
+
+    #[test]
+    fn fast_diagonal_move_slides_along_wall() {
+        let world = default_map_world();
+        let (x, y) = slide(&world, 224.0, 100.0, FAST * 0.5, FAST * 0.5);
+
+        // Blocked vertically by the top wall but keeps most of its sideways motion
+        assert!(y <= 128.0 - PLAYER_RADIUS + TOLERANCE, "crossed the wall to y {y}");
+        assert!(x > 224.0 + FAST * 0.25, "only slid to x {x}");
+        assert_clear_of_walls(&world, x, y);
+    }
+
+    #[test]
+    fn fast_move_stops_at_circle() {
+        let world = default_map_world();
+        let (x, y) = slide(&world, 128.0, 150.0, 0.0, FAST);
+
+        assert!(y <= 256.0 - 20.0 - PLAYER_RADIUS + TOLERANCE, "passed into the circle to y {y}");
+        assert_clear_of_walls(&world, x, y);
+    }
+
+    #[test]
+    fn fast_moves_never_end_inside_walls() {
+        let world = default_map_world();
+        for step in 0..32 {
+            let angle = step as f32 / 32.0 * std::f32::consts::TAU;
+            let (x, y) = slide(&world, 256.0, 100.0, angle.cos() * FAST, angle.sin() * FAST);
+            assert_clear_of_walls(&world, x, y);
+        }
+    }
+}
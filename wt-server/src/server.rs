@@ -1,5 +1,5 @@
-use anyhow::Result;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use anyhow::{bail, Result};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use std::time::Duration;
 use tracing::error;
 use tracing::info;
@@ -7,7 +7,7 @@ use tracing::info_span;
 use tracing::Instrument;
 use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::EnvFilter;
-use wtransport::endpoint::IncomingSession;
+use wtransport::endpoint::{IncomingSession, SessionRequest};
 use wtransport::Endpoint;
 use wtransport::Identity;
 use wtransport::ServerConfig;
@@ -16,13 +16,20 @@ use dashmap::DashMap;
 use std::sync::Arc;
 use crate::messages::{ServerToWorld, WorldToServer};
 use crate::network::*;
+use crate::rooms::{room_id, Rooms};
 type ConnectionId = Uuid;
-type ConnectionMap = Arc<DashMap<ConnectionId, wtransport::Connection>>;
+type ConnectionMap = Arc<DashMap<ConnectionId, ClientConnection>>;
+
+// `reliable` feeds the connection's ordered stream, see `write_reliable`
+struct ClientConnection {
+    connection: wtransport::Connection,
+    reliable: UnboundedSender<Vec<u8>>,
+}
 
 
 
 pub async fn run_server(
-    to_world: UnboundedSender<ServerToWorld>,
+    rooms: Arc<Rooms>,
     mut from_world: UnboundedReceiver<WorldToServer>,
 ) -> Result<()> {
     init_logging();
@@ -48,30 +55,39 @@ pub async fn run_server(
                     incoming_session,
                     connections.clone(),
                     connection_id,
-                    to_world.clone(),
+                    rooms.clone(),
                 ).instrument(info_span!("Connection", %connection_id)));
             }
             // Process messages from the world
             Some(msg) = from_world.recv() => {
                 match msg {
                     WorldToServer::SendTick { receiver_connection_id, tick } => {
-                        if let Some(connection) = connections.get(&receiver_connection_id) {
-                            let message = build_tick_datagram(tick);
-                            connection.send_datagram(message)?;
-                        }
+                        send_datagram(&connections, receiver_connection_id, build_tick_datagram(tick));
                     }
-                    WorldToServer::CreatePlayer { receiver_connection_id, connection_id, x, y } => {
-                        if let Some(connection) = connections.get(&receiver_connection_id) {
-                            let message = build_create_player_datagram(connection_id, x, y);
-                            let mut stream = connection.open_uni().await?.await?;
-                            stream.write_all(&message).await?;
+                    WorldToServer::SendMap { receiver_connection_id, map } => {
+                        // Maps are checked when loaded, so this only fails if that check was skipped
+                        match build_map_datagram(&map) {
+                            Ok(message) => send_reliable(&connections, receiver_connection_id, message),
+                            Err(err) => error!("Failed to encode map: {:?}", err),
                         }
                     }
-                    WorldToServer::UpdatePlayerPosition { receiver_connection_id, connection_id, x, y } => {
-                        if let Some(connection) = connections.get(&receiver_connection_id) {
-                            let message = build_update_player_position_datagram(connection_id, x, y);
-                            connection.send_datagram(message)?;
-                        }
+                    WorldToServer::SpawnEntity { receiver_connection_id, network_id, archetype, local, count, components } => {
+                        send_reliable(&connections, receiver_connection_id, build_spawn_entity_datagram(network_id, archetype, local, count, &components));
+                    }
+                    WorldToServer::DespawnEntity { receiver_connection_id, network_id } => {
+                        send_reliable(&connections, receiver_connection_id, build_despawn_entity_datagram(network_id));
+                    }
+                    WorldToServer::UpdateEntity { receiver_connection_id, network_id, count, components } => {
+                        send_datagram(&connections, receiver_connection_id, build_update_entity_datagram(network_id, count, &components));
+                    }
+                    WorldToServer::Hit { receiver_connection_id, target, attacker, x, y } => {
+                        send_reliable(&connections, receiver_connection_id, build_hit_datagram(target, attacker, x, y));
+                    }
+                    WorldToServer::Eliminated { receiver_connection_id, target, attacker } => {
+                        send_reliable(&connections, receiver_connection_id, build_eliminated_datagram(target, attacker));
+                    }
+                    WorldToServer::MatchState { receiver_connection_id, state } => {
+                        send_reliable(&connections, receiver_connection_id, build_match_state_datagram(&state));
                     }
                 }
             }
@@ -83,14 +99,47 @@ pub async fn run_server(
     }
 }
 
+// A send that fails only affects that connection, whose own task notices it closing and cleans up
+fn send_reliable(connections: &ConnectionMap, connection_id: ConnectionId, message: Vec<u8>) {
+    if let Some(connection) = connections.get(&connection_id) {
+        // Only fails once the writer has stopped, which it has already logged
+        let _ = connection.reliable.send(message);
+    }
+}
+
+fn send_datagram(connections: &ConnectionMap, connection_id: ConnectionId, message: Vec<u8>) {
+    let Some(connection) = connections.get(&connection_id) else {
+        return;
+    };
+    if let Err(err) = connection.connection.send_datagram(message) {
+        info!("Datagram to {} failed: {:?}", connection_id, err);
+    }
+}
+
+// Reliable messages share one uni stream per connection, so the client applies them in the order the
+// world sent them; separate streams can overtake each other, letting a despawn arrive before its
+// spawn. Each message is framed by its length as a u32.
+async fn write_reliable(connection: wtransport::Connection, mut messages: UnboundedReceiver<Vec<u8>>) {
+    let result = async {
+        let mut stream = connection.open_uni().await?.await?;
+        while let Some(message) = messages.recv().await {
+            stream.write_all(&(message.len() as u32).to_le_bytes()).await?;
+            stream.write_all(&message).await?;
+        }
+        anyhow::Ok(())
+    }.await;
+    if let Err(err) = result {
+        info!("Reliable stream closed: {:?}", err);
+    }
+}
 
 async fn handle_connection(
     incoming_session: IncomingSession,
     connections: ConnectionMap,
     connection_id: ConnectionId,
-    to_world: UnboundedSender<ServerToWorld>,
+    rooms: Arc<Rooms>,
 ) {
-    let result = handle_connection_impl(incoming_session, connections, connection_id, to_world).await;
+    let result = handle_connection_impl(incoming_session, connections, connection_id, rooms).await;
     error!("{:?}", result);
 }
 
@@ -98,16 +147,44 @@ async fn handle_connection_impl(
     incoming_session: IncomingSession,
     connections: ConnectionMap,
     connection_id: ConnectionId,
+    rooms: Arc<Rooms>,
+) -> Result<()> {
+    let session_request = incoming_session.await?;
+    let Some(room_id) = room_id(session_request.path()) else {
+        session_request.not_found().await;
+        bail!("invalid room path");
+    };
+    let team = requested_team(session_request.path());
+    let Some(to_world) = rooms.join(&room_id) else {
+        session_request.too_many_requests().await;
+        bail!("room limit reached");
+    };
+    info!("Joined room '{}'", room_id);
+
+    let result = run_session(session_request, connections.clone(), connection_id, to_world.clone(), team).await;
+
+    if connections.remove(&connection_id).is_some() {
+        let _ = to_world.send(ServerToWorld::PlayerLeft { connection_id });
+    }
+    rooms.leave(&room_id);
+    result
+}
+
+async fn run_session(
+    session_request: SessionRequest,
+    connections: ConnectionMap,
+    connection_id: ConnectionId,
     to_world: UnboundedSender<ServerToWorld>,
+    team: Option<u8>,
 ) -> Result<()> {
     let mut buffer = vec![0; 65536].into_boxed_slice();
 
-    let session_request = incoming_session.await?;
-
     let connection = session_request.accept().await?;
 
-    connections.insert(connection_id, connection.clone());
-    to_world.send(ServerToWorld::PlayerJoined { connection_id })?;
+    let (reliable, messages) = mpsc::unbounded_channel();
+    tokio::spawn(write_reliable(connection.clone(), messages).in_current_span());
+    connections.insert(connection_id, ClientConnection { connection: connection.clone(), reliable });
+    to_world.send(ServerToWorld::PlayerJoined { connection_id, team })?;
 
     loop {
         tokio::select! {
@@ -149,8 +226,18 @@ async fn handle_connection_impl(
                     info!("Empty datagram received");
                 } else {
                     match ClientToServerMessage::from_u8(dgram[0]) {
+                        // A closed world channel means the room has stopped, so the session ends with it
                         Some(ClientToServerMessage::InputClickPressed) => {
-                            decode_input_click_pressed(connection_id, to_world.clone(), &dgram);
+                            decode_input_click_pressed(connection_id, to_world.clone(), &dgram)?;
+                        }
+                        Some(ClientToServerMessage::InputClickHold) => {
+                            decode_input_click_hold(connection_id, to_world.clone(), &dgram)?;
+                        }
+                        Some(ClientToServerMessage::InputClickReleased) => {
+                            decode_input_click_released(connection_id, to_world.clone(), &dgram)?;
+                        }
+                        Some(ClientToServerMessage::InputCommand) => {
+                            decode_input_command(connection_id, to_world.clone(), &dgram)?;
                         }
 
                         None => {
@@ -163,6 +250,14 @@ async fn handle_connection_impl(
     }
 }
 
+// Clients pick a team with `?team=<id>` on the session URL; anything else is auto-balanced
+fn requested_team(path: &str) -> Option<u8> {
+    let (_, query) = path.split_once('?')?;
+    query.split('&')
+        .find_map(|pair| pair.strip_prefix("team="))
+        .and_then(|team| team.parse().ok())
+}
+
 fn init_logging() {
     let env_filter = EnvFilter::builder()
         .with_default_directive(LevelFilter::INFO.into())
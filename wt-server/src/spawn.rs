@@ -0,0 +1,110 @@
+use hecs::{Entity, World};
+
+use crate::broadphase::CollisionGrid;
+use crate::components::*;
+use crate::scripts::{circle_overlaps_region, shape_distance};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnPolicy {
+    RoundRobin,
+    FarthestFromPlayers,
+    Team,
+}
+
+impl std::str::FromStr for SpawnPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "round_robin" => Ok(SpawnPolicy::RoundRobin),
+            "farthest" => Ok(SpawnPolicy::FarthestFromPlayers),
+            "team" => Ok(SpawnPolicy::Team),
+            _ => Err(anyhow::anyhow!("unknown spawn policy '{}'", value)),
+        }
+    }
+}
+
+struct Candidate {
+    x: f32,
+    y: f32,
+    nearest_player_squared: f32,
+    free: bool,
+}
+
+// `spawning` is the player being placed, if they already exist; where they were doesn't occupy anything
+pub fn choose_spawn_point(world: &mut World, policy: SpawnPolicy, team: Option<u8>, radius: f32, spawning: Option<Entity>) -> (f32, f32) {
+    let players: Vec<(f32, f32, f32)> = world.query::<(&Position, &PlayerCollision)>().iter()
+        .filter(|&(entity, _)| Some(entity) != spawning)
+        .map(|(_, (position, collision))| (position.x + collision.offset_x, position.y + collision.offset_y, collision.radius))
+        .collect();
+
+    // Spawning inside another team's region would only get the player pushed straight back out
+    let restricted: Vec<Region> = world.query::<&Region>().iter()
+        .filter(|(_, region)| region.team.is_some() && region.team != team)
+        .map(|(_, region)| *region)
+        .collect();
+
+    let mut candidates = Vec::new();
+    for (_, spawn_point) in world.query::<&SpawnPoint>().iter() {
+        if policy == SpawnPolicy::Team && spawn_point.team.is_some() && spawn_point.team != team {
+            continue;
+        }
+
+        let mut nearest_player_squared = f32::MAX;
+        let mut free = true;
+        for &(x, y, player_radius) in &players {
+            let dx = x - spawn_point.x;
+            let dy = y - spawn_point.y;
+            let distance_squared = dx * dx + dy * dy;
+            nearest_player_squared = nearest_player_squared.min(distance_squared);
+            if distance_squared < (radius + player_radius) * (radius + player_radius) {
+                free = false;
+            }
+        }
+
+        if restricted.iter().any(|region| circle_overlaps_region(spawn_point.x, spawn_point.y, radius, region)) {
+            free = false;
+        }
+
+        for (_, grid) in world.query::<&CollisionGrid>().iter() {
+            if grid.query_circle(spawn_point.x, spawn_point.y, radius).iter().any(|shape| shape_distance(spawn_point.x, spawn_point.y, shape) < radius) {
+                free = false;
+            }
+        }
+
+        candidates.push(Candidate { x: spawn_point.x, y: spawn_point.y, nearest_player_squared, free });
+    }
+
+    if candidates.is_empty() {
+        let (width, height) = world.query::<&MapBounds>().iter()
+            .next()
+            .map_or((0.0, 0.0), |(_, bounds)| (bounds.width, bounds.height));
+        return (width / 2.0, height / 2.0);
+    }
+
+    // Query order isn't stable across spawns and despawns, so order the points by position
+    candidates.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+
+    let chosen = match policy {
+        SpawnPolicy::RoundRobin => {
+            let mut next = 0;
+            for (_, rotation) in world.query_mut::<&mut SpawnRotation>() {
+                next = rotation.next;
+                rotation.next = rotation.next.wrapping_add(1);
+            }
+
+            let start = next % candidates.len();
+            (0..candidates.len())
+                .map(|offset| &candidates[(start + offset) % candidates.len()])
+                .find(|candidate| candidate.free)
+                .unwrap_or(&candidates[start])
+        }
+        SpawnPolicy::FarthestFromPlayers | SpawnPolicy::Team => {
+            candidates.iter()
+                .max_by(|a, b| a.free.cmp(&b.free).then(a.nearest_player_squared.total_cmp(&b.nearest_player_squared)))
+                .unwrap()
+        }
+    };
+
+    (chosen.x, chosen.y)
+}
@@ -1,10 +1,27 @@
+use crate::broadphase::CollisionGrid;
 use crate::components::*;
+use crate::config::WorldConfig;
+use crate::index::{ConnectionIndex, NetworkIndex};
+use crate::interest::SpatialGrid;
+use crate::navigation::NavGrid;
 use crate::scripts::*;
-use hecs::World;
+use crate::spawn::choose_spawn_point;
+use crate::teams::assign_team;
+use hecs::{Entity, World};
+use std::collections::{HashMap, HashSet, VecDeque};
 use uuid::Uuid;
 
 use tokio::sync::mpsc::UnboundedSender;
-use crate::messages::WorldToServer;
+use crate::messages::{InputCommand, WorldToServer, BUTTON_PRIMARY, BUTTON_THROW};
+use crate::network::update_entity_size;
+use crate::replication::ReplicationRegistry;
+
+pub const PLAYER_RADIUS: f32 = 16.0;
+pub const SLIDE_ITERATIONS: u8 = 4;
+pub const SEPARATION_ITERATIONS: u8 = 3;
+pub const WAYPOINT_REACHED_DISTANCE: f32 = 1.0;
+// Releases travel as datagrams, so a held input that stops arriving counts as released
+pub const HOLD_TIMEOUT_TICKS: u64 = 10;
 
 pub fn update_tick(world: &mut World, to_server: UnboundedSender<WorldToServer>) {
     let mut tick_value = 0;
@@ -23,66 +40,213 @@ pub fn update_tick(world: &mut World, to_server: UnboundedSender<WorldToServer>)
     }
 }
 
-pub fn create_player(world: &mut World, to_server: UnboundedSender<WorldToServer>, connection_id: Uuid, x: f32, y: f32) {
-    world.spawn((
+pub fn create_player(world: &mut World, index: &mut ConnectionIndex, network_index: &mut NetworkIndex, config: &WorldConfig, connection_id: Uuid, requested_team: Option<u8>) {
+    let team = assign_team(world, config, requested_team);
+    let (x, y) = choose_spawn_point(world, config.spawn_policy, Some(team.id), PLAYER_RADIUS, None);
+    let entity = world.spawn((
         Player,
-        Connection {connection_id: connection_id},
+        Connection {connection_id},
+        Interest {view_radius: config.view_radius, visible: HashSet::new()},
+        Replication {bytes_per_tick: config.bandwidth_per_tick, last_sent_tick: HashMap::new(), last_sent: HashMap::new()},
         State {state: PlayerState::Idle},
-        Position { x: x, y: y},
+        Facing {angle: 0.0},
+        Position { x, y},
         Velocity { x: 0.0, y: 0.0 },
-        MoveTarget {x: x, y: y},
-        PlayerCollision { radius: 16.0, offset_x: 0.0, offset_y: 0.0 },
-        PlayerMove {move_speed: 2.0, move_input_type: MovementType::Target, timer: 0, }, //timer_threshold: 10, direction_radius: 24.0
+        MoveTarget {x, y},
+        Path {waypoints: VecDeque::new()},
+        PlayerInput {buttons: 0, moving: false},
+        PlayerCollision { radius: PLAYER_RADIUS, offset_x: 0.0, offset_y: 0.0 },
+        PlayerMove {move_speed: 2.0, move_input_type: MovementType::Target, timer: 0, timer_threshold: 10, direction_radius: 24.0, last_hold_tick: 0},
     ));
-    println!("Player {} Created at X {}, Y {}", connection_id, x, y);
-    
-    for (_,(
-        connection,
-        _,
-        position,
-    )) in world.query::<(
-        &Connection,
-        &Player,
-        &Position,
-    )>().iter() {
-        //Create the new player for exisitng connections
-        to_server.send(WorldToServer::CreatePlayer {
-            receiver_connection_id: connection.connection_id,
-            connection_id: connection_id,
-            x: x,
-            y: y,
-        }).unwrap();
-        
-        //Create existing players to new player
-        if connection_id != connection.connection_id {
-            to_server.send(WorldToServer::CreatePlayer {
-                receiver_connection_id: connection_id,
-                connection_id: connection.connection_id,
-                x: position.x,
-                y: position.y,
+    // hecs bundles top out at 15 components, so combat state goes on separately
+    let _ = world.insert(entity, (
+        PlayerThrow {aim: None, view_tick: 0, ready_tick: 0},
+        Health {current: config.player_max_health, max: config.player_max_health},
+        PositionHistory {samples: VecDeque::with_capacity(config.max_rewind_ticks as usize + 1)},
+        team,
+    ));
+    index.insert(connection_id, entity);
+    make_networked(world, network_index, entity, Archetype::Player);
+    println!("Player {} Created on team {} at X {}, Y {}", connection_id, team.id, x, y);
+}
+
+pub fn despawn_player(world: &mut World, index: &mut ConnectionIndex, network_index: &mut NetworkIndex, to_server: UnboundedSender<WorldToServer>, connection_id: Uuid) {
+    let Some(entity) = index.remove(connection_id) else {
+        return;
+    };
+    despawn_networked(world, network_index, to_server, entity);
+    println!("Player {} Despawned", connection_id);
+}
+
+// Networked entities are picked up by `update_interest` and spawned on clients as they come into view
+pub fn make_networked(world: &mut World, network_index: &mut NetworkIndex, entity: Entity, archetype: Archetype) -> NetworkId {
+    let network_id = network_index.allocate(entity);
+    let _ = world.insert(entity, (network_id, archetype));
+    network_id
+}
+
+pub fn despawn_networked(world: &mut World, network_index: &mut NetworkIndex, to_server: UnboundedSender<WorldToServer>, entity: Entity) {
+    let network_id = world.get::<&NetworkId>(entity).ok().map(|network_id| *network_id);
+    let _ = world.despawn(entity);
+    let Some(network_id) = network_id else {
+        return;
+    };
+    network_index.remove(network_id);
+
+    for (_, (connection, interest)) in world.query_mut::<(&Connection, &mut Interest)>() {
+        if interest.visible.remove(&network_id) {
+            to_server.send(WorldToServer::DespawnEntity {
+                receiver_connection_id: connection.connection_id,
+                network_id,
             }).unwrap();
         }
     }
 }
 
-pub fn input_click_pressed(world: &mut World, connection_id: Uuid, x: f32, y: f32) {
+pub fn input_click_pressed(world: &mut World, index: &ConnectionIndex, connection_id: Uuid, x: f32, y: f32) {
+    let Some(entity) = index.get(connection_id) else {
+        return;
+    };
+
+    let waypoints = {
+        let Ok(position) = world.get::<&Position>(entity) else {
+            return;
+        };
+        let team = world.get::<&Team>(entity).ok().map(|team| team.id);
+        let mut nav_query = world.query::<&NavGrid>();
+        nav_query.iter().next()
+            .and_then(|(_, nav_grid)| nav_grid.find_path((position.x, position.y), (x, y), team))
+            .unwrap_or_else(|| vec![(x, y)])
+    };
+
+    if let Ok((target, path, move_type)) = world.query_one_mut::<(&mut MoveTarget, &mut Path, &mut PlayerMove)>(entity) {
+        move_type.move_input_type = MovementType::Target;
+        move_type.timer = 0;
+        path.waypoints = waypoints.into();
+        if let Some((next_x, next_y)) = path.waypoints.pop_front() {
+            target.x = next_x;
+            target.y = next_y;
+        }
+    }
+}
+
+pub fn input_click_hold(world: &mut World, index: &ConnectionIndex, connection_id: Uuid, x: f32, y: f32) {
+    let Some(entity) = index.get(connection_id) else {
+        return;
+    };
+    let tick_value = current_tick(world);
+
+    if let Ok((target, path, move_type)) = world.query_one_mut::<(&mut MoveTarget, &mut Path, &mut PlayerMove)>(entity) {
+        move_type.timer = move_type.timer.saturating_add(1);
+        move_type.last_hold_tick = tick_value;
+
+        // A short click stays a click, holding past the threshold steers towards the pointer
+        if move_type.timer >= move_type.timer_threshold {
+            move_type.move_input_type = MovementType::Direction;
+            path.waypoints.clear();
+            target.x = x;
+            target.y = y;
+        }
+    }
+}
+
+pub fn input_click_released(world: &mut World, index: &ConnectionIndex, connection_id: Uuid) {
+    let Some(entity) = index.get(connection_id) else {
+        return;
+    };
+
+    if let Ok((position, target, move_type)) = world.query_one_mut::<(&Position, &mut MoveTarget, &mut PlayerMove)>(entity) {
+        release_direction(position, target, move_type);
+    }
+}
+
+pub fn input_command(world: &mut World, index: &ConnectionIndex, connection_id: Uuid, command: InputCommand) {
+    let Some(entity) = index.get(connection_id) else {
+        return;
+    };
+    let tick_value = current_tick(world);
+
+    let primary_pressed = {
+        let Ok((position, target, path, move_type, input, throw)) = world.query_one_mut::<(&Position, &mut MoveTarget, &mut Path, &mut PlayerMove, &mut PlayerInput, &mut PlayerThrow)>(entity) else {
+            return;
+        };
+
+        let moving = command.move_x != 0 || command.move_y != 0;
+        if moving {
+            // Steer towards a point beyond the dead zone in the commanded direction
+            let dx = f32::from(command.move_x) / 127.0;
+            let dy = f32::from(command.move_y) / 127.0;
+            let length = (dx * dx + dy * dy).sqrt();
+            let reach = move_type.direction_radius * 4.0;
+
+            move_type.move_input_type = MovementType::Direction;
+            move_type.last_hold_tick = tick_value;
+            path.waypoints.clear();
+            target.x = position.x + dx / length * reach;
+            target.y = position.y + dy / length * reach;
+        } else if input.moving {
+            release_direction(position, target, move_type);
+        }
+
+        let primary_pressed = command.buttons & BUTTON_PRIMARY != 0 && input.buttons & BUTTON_PRIMARY == 0;
+        if command.buttons & BUTTON_THROW != 0 && input.buttons & BUTTON_THROW == 0 {
+            throw.aim = Some((command.pointer_x, command.pointer_y));
+            throw.view_tick = command.view_tick;
+        }
+        input.buttons = command.buttons;
+        input.moving = moving;
+        primary_pressed
+    };
+
+    if primary_pressed {
+        input_click_pressed(world, index, connection_id, command.pointer_x, command.pointer_y);
+    }
+}
+
+pub fn expire_held_input(world: &mut World) {
+    let tick_value = current_tick(world);
+
+    for (_, (position, target, move_type)) in world.query_mut::<(&Position, &mut MoveTarget, &mut PlayerMove)>() {
+        if move_type.move_input_type == MovementType::Direction && tick_value.saturating_sub(move_type.last_hold_tick) > HOLD_TIMEOUT_TICKS {
+            release_direction(position, target, move_type);
+        }
+    }
+}
+
+fn release_direction(position: &Position, target: &mut MoveTarget, move_type: &mut PlayerMove) {
+    if move_type.move_input_type == MovementType::Direction {
+        target.x = position.x;
+        target.y = position.y;
+    }
+    move_type.move_input_type = MovementType::Target;
+    move_type.timer = 0;
+}
+
+pub fn current_tick(world: &World) -> u64 {
+    world.query::<&Tick>().iter().next().map_or(0, |(_, tick)| tick.tick)
+}
+
+pub fn follow_path(world: &mut World) {
     for (_,(
-        connection,
-        _,
+        position,
         target,
-        move_type,
+        path,
     )) in world.query_mut::<(
-        &Connection,
-        &Player,
+        &Position,
         &mut MoveTarget,
-        &mut PlayerMove,
+        &mut Path,
     )>() {
-        if connection.connection_id == connection_id {
-            move_type.move_input_type = MovementType::Target;
-            move_type.timer = 0;
-            target.x = x;
-            target.y = y;
-            break;
+        if path.waypoints.is_empty() {
+            continue;
+        }
+
+        let dx = target.x - position.x;
+        let dy = target.y - position.y;
+        if dx * dx + dy * dy <= WAYPOINT_REACHED_DISTANCE * WAYPOINT_REACHED_DISTANCE
+            && let Some((next_x, next_y)) = path.waypoints.pop_front()
+        {
+            target.x = next_x;
+            target.y = next_y;
         }
     }
 }
@@ -92,13 +256,21 @@ pub fn update_state(world: &mut World) {
         _,
         state,
         position,
-        target
+        target,
+        eliminated
     )) in world.query::<(
         &Player,
         &mut State,
         &Position,
-        &MoveTarget,
+        &mut MoveTarget,
+        Option<&Eliminated>
     )>().iter() {
+        // Eliminated players stay where they fell until they respawn
+        if eliminated.is_some() {
+            target.x = position.x;
+            target.y = position.y;
+        }
+
         let dx = target.x - position.x;
         let dy = target.y - position.y;
         let distance = (dx * dx + dy * dy).sqrt();
@@ -113,6 +285,11 @@ pub fn update_state(world: &mut World) {
 }
 
 pub fn handle_state(world: &mut World) {
+    let mut grid_query = world.query::<&CollisionGrid>();
+    let grid = grid_query.iter().next().map(|(_, grid)| grid);
+    let regions = team_regions(world);
+    let bounds = map_bounds(world);
+
     for (_,(
         _,
         state,
@@ -121,6 +298,7 @@ pub fn handle_state(world: &mut World) {
         target,
         player_collision,
         player_move,
+        team,
     )) in world.query::<(
         &Player,
         &mut State,
@@ -128,7 +306,8 @@ pub fn handle_state(world: &mut World) {
         &mut Velocity,
         &mut MoveTarget,
         &PlayerCollision,
-        &PlayerMove
+        &PlayerMove,
+        &Team
     )>().iter() {
         match state.state {
             PlayerState::Idle => {
@@ -143,7 +322,16 @@ pub fn handle_state(world: &mut World) {
                 let dy = target.y - position.y;
                 let length = (dx * dx + dy * dy).sqrt();
 
-                if length > player_move.move_speed {
+                if player_move.move_input_type == MovementType::Direction {
+                    // Steer at full speed while the pointer is outside the dead zone around the player
+                    if length > player_move.direction_radius {
+                        velocity.x = dx / length * player_move.move_speed;
+                        velocity.y = dy / length * player_move.move_speed;
+                    } else {
+                        velocity.x = 0.0;
+                        velocity.y = 0.0;
+                    }
+                } else if length > player_move.move_speed {
                     velocity.x = dx / length * player_move.move_speed;
                     velocity.y = dy / length * player_move.move_speed;
                 } else {
@@ -151,12 +339,25 @@ pub fn handle_state(world: &mut World) {
                     velocity.y = dy;
                 }
                 
-                for (_, collision) in world.query::<&Collision>().iter() {
-                    let (vx, vy) = collision_slide_velocity(&position, &velocity, &player_collision, &collision, 4);
+                if let Some(grid) = grid {
+                    // Each slide iteration can push the circle out by up to its radius
+                    let reach = player_collision.radius * (SLIDE_ITERATIONS as f32 + 1.0) + player_move.move_speed;
+                    let shapes = grid.query_circle(position.x + player_collision.offset_x, position.y + player_collision.offset_y, reach);
+                    let (vx, vy) = collision_slide_velocity(position, velocity, player_collision, &shapes, SLIDE_ITERATIONS);
                     velocity.x = vx;
                     velocity.y = vy;
                 }
 
+                if !regions.is_empty() {
+                    let (x, y) = keep_out_of_regions(
+                        position.x + player_collision.offset_x + velocity.x,
+                        position.y + player_collision.offset_y + velocity.y,
+                        player_collision.radius, team.id, &regions, bounds,
+                    );
+                    velocity.x = x - player_collision.offset_x - position.x;
+                    velocity.y = y - player_collision.offset_y - position.y;
+                }
+
                 if velocity.x == 0.0 && velocity.y == 0.0 {
                     target.x = position.x;
                     target.y = position.y;
@@ -166,44 +367,599 @@ pub fn handle_state(world: &mut World) {
     }
 }
 
+struct Body {
+    entity: Entity,
+    connection_id: Uuid,
+    x: f32,
+    y: f32,
+    radius: f32,
+    moving: bool,
+}
+
+pub fn resolve_player_collisions(world: &mut World, grid: &mut SpatialGrid) {
+    let mut bodies: Vec<Body> = world.query::<(
+        &Player,
+        &Connection,
+        &Position,
+        &Velocity,
+        &PlayerCollision,
+    )>().without::<&Eliminated>().iter().map(|(entity, (_, connection, position, velocity, player_collision))| Body {
+        entity,
+        connection_id: connection.connection_id,
+        x: position.x + player_collision.offset_x + velocity.x,
+        y: position.y + player_collision.offset_y + velocity.y,
+        radius: player_collision.radius,
+        moving: velocity.x != 0.0 || velocity.y != 0.0,
+    }).collect();
+
+    // Sorting by connection id keeps the result independent of query order
+    bodies.sort_by_key(|body| body.connection_id);
+    let indices: HashMap<Entity, usize> = bodies.iter().enumerate().map(|(i, body)| (body.entity, i)).collect();
+    let max_radius = bodies.iter().fold(0.0f32, |max, body| max.max(body.radius));
+
+    let mut total_corrections = vec![(0.0f32, 0.0f32); bodies.len()];
+    for _ in 0..SEPARATION_ITERATIONS {
+        grid.clear();
+        for body in &bodies {
+            grid.insert(body.entity, body.x, body.y);
+        }
+
+        // Every overlapping pair is resolved against the same positions, then all corrections apply at once
+        let mut corrections = vec![(0.0f32, 0.0f32); bodies.len()];
+        let mut overlapping = false;
+        for (i, body) in bodies.iter().enumerate() {
+            let mut neighbours: Vec<usize> = grid.query_radius(body.x, body.y, body.radius + max_radius)
+                .into_iter()
+                .filter_map(|entity| indices.get(&entity).copied())
+                .filter(|&j| j > i)
+                .collect();
+            neighbours.sort_unstable();
+
+            for j in neighbours {
+                let other = &bodies[j];
+                let (nx, ny, penetration) = circle_separation(body.x, body.y, body.radius, other.x, other.y, other.radius);
+                if penetration <= 0.0 {
+                    continue;
+                }
+                overlapping = true;
+
+                // Moving players are blocked by idle ones, otherwise the push is shared
+                let (weight_i, weight_j) = match (body.moving, other.moving) {
+                    (true, false) => (1.0, 0.0),
+                    (false, true) => (0.0, 1.0),
+                    _ => (0.5, 0.5),
+                };
+                corrections[i].0 -= nx * penetration * weight_i;
+                corrections[i].1 -= ny * penetration * weight_i;
+                corrections[j].0 += nx * penetration * weight_j;
+                corrections[j].1 += ny * penetration * weight_j;
+            }
+        }
+
+        if !overlapping {
+            break;
+        }
+
+        for (i, body) in bodies.iter_mut().enumerate() {
+            body.x += corrections[i].0;
+            body.y += corrections[i].1;
+            total_corrections[i].0 += corrections[i].0;
+            total_corrections[i].1 += corrections[i].1;
+        }
+    }
+
+    let mut grid_query = world.query::<&CollisionGrid>();
+    let collision_grid = grid_query.iter().next().map(|(_, grid)| grid);
+
+    for (i, body) in bodies.iter().enumerate() {
+        let (cx, cy) = total_corrections[i];
+        if cx == 0.0 && cy == 0.0 {
+            continue;
+        }
+
+        let Ok(mut query) = world.query_one::<(&Position, &mut Velocity, &mut MoveTarget, &PlayerCollision)>(body.entity) else {
+            continue;
+        };
+        let Some((position, velocity, target, player_collision)) = query.get() else {
+            continue;
+        };
+
+        velocity.x += cx;
+        velocity.y += cy;
+
+        // Being pushed must not shove a player through a wall
+        if let Some(collision_grid) = collision_grid {
+            let speed = (velocity.x * velocity.x + velocity.y * velocity.y).sqrt();
+            let reach = player_collision.radius * (SLIDE_ITERATIONS as f32 + 1.0) + speed;
+            let shapes = collision_grid.query_circle(position.x + player_collision.offset_x, position.y + player_collision.offset_y, reach);
+            let (vx, vy) = collision_slide_velocity(position, velocity, player_collision, &shapes, SLIDE_ITERATIONS);
+            velocity.x = vx;
+            velocity.y = vy;
+        }
+
+        // An idle player that was pushed stays where it ends up instead of walking back
+        if !body.moving {
+            target.x = position.x + velocity.x;
+            target.y = position.y + velocity.y;
+        }
+    }
+}
+
+fn map_bounds(world: &World) -> Option<(f32, f32)> {
+    world.query::<&MapBounds>().iter().next().map(|(_, bounds)| (bounds.width, bounds.height))
+}
+
+fn team_regions(world: &World) -> Vec<Region> {
+    world.query::<&Region>().iter()
+        .filter(|(_, region)| region.team.is_some())
+        .map(|(_, region)| *region)
+        .collect()
+}
+
 pub fn apply_velocity(world: &mut World) {
+    let bounds = map_bounds(world);
+    let regions = team_regions(world);
+
     for (_,(
         _,
         position,
+        facing,
         velocity,
-        _
+        player_collision,
+        team
     )) in world.query::<(
         &Player,
         &mut Position,
+        &mut Facing,
         &Velocity,
-        &PlayerCollision
+        &PlayerCollision,
+        &Team
     )>().iter() {
         position.x += velocity.x;
         position.y += velocity.y;
+
+        if velocity.x != 0.0 || velocity.y != 0.0 {
+            facing.angle = velocity.y.atan2(velocity.x);
+        }
+
+        if let Some((width, height)) = bounds {
+            position.x = position.x.clamp(player_collision.radius - player_collision.offset_x, width - player_collision.radius - player_collision.offset_x);
+            position.y = position.y.clamp(player_collision.radius - player_collision.offset_y, height - player_collision.radius - player_collision.offset_y);
+        }
+
+        // Pushes from other players can still shove someone over a team line
+        if !regions.is_empty() {
+            let (x, y) = keep_out_of_regions(
+                position.x + player_collision.offset_x,
+                position.y + player_collision.offset_y,
+                player_collision.radius, team.id, &regions, bounds,
+            );
+            position.x = x - player_collision.offset_x;
+            position.y = y - player_collision.offset_y;
+        }
+    }
+}
+
+// Runs after movement so each tick's sample is where the player ended up that tick
+pub fn record_position_history(world: &mut World, config: &WorldConfig) {
+    let tick_value = current_tick(world);
+    for (_, (position, history)) in world.query_mut::<(&Position, &mut PositionHistory)>() {
+        history.samples.push_back((tick_value, position.x, position.y));
+        while history.samples.len() as u64 > config.max_rewind_ticks + 1 {
+            history.samples.pop_front();
+        }
+    }
+}
+
+pub fn throw_projectiles(world: &mut World, network_index: &mut NetworkIndex, config: &WorldConfig) {
+    let tick_value = current_tick(world);
+
+    let mut throws = Vec::new();
+    for (entity, (position, facing, throw)) in world.query_mut::<(&Position, &Facing, &mut PlayerThrow)>().without::<&Eliminated>() {
+        let Some((aim_x, aim_y)) = throw.aim.take() else {
+            continue;
+        };
+        if tick_value < throw.ready_tick {
+            continue;
+        }
+        throw.ready_tick = tick_value + config.throw_cooldown_ticks;
+        // Clients can claim any tick, so the rewind is capped to keep hits on the past bounded
+        let rewind_ticks = tick_value.saturating_sub(throw.view_tick).min(config.max_rewind_ticks);
+
+        // Aiming at the thrower itself (e.g. a gamepad with no pointer) throws the way they face
+        let mut dx = aim_x - position.x;
+        let mut dy = aim_y - position.y;
+        let length = (dx * dx + dy * dy).sqrt();
+        if length < 1.0 {
+            (dy, dx) = facing.angle.sin_cos();
+        } else {
+            dx /= length;
+            dy /= length;
+        }
+        throws.push((entity, position.x, position.y, dx, dy, rewind_ticks));
+    }
+
+    for (owner, x, y, dx, dy, rewind_ticks) in throws {
+        let entity = world.spawn((
+            Projectile {
+                owner,
+                radius: config.projectile_radius,
+                expires_tick: tick_value + config.projectile_lifetime_ticks,
+                bounces_remaining: config.projectile_bounces,
+                rewind_ticks,
+            },
+            Position { x, y },
+            Velocity { x: dx * config.projectile_speed, y: dy * config.projectile_speed },
+        ));
+        make_networked(world, network_index, entity, Archetype::Projectile);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    pub target: Entity,
+    pub attacker: Entity,
+    pub x: f32,
+    pub y: f32,
+}
+
+// Moves projectiles one tick, bouncing off or stopping at walls and ending on the first player hit
+pub fn update_projectiles(world: &mut World, network_index: &mut NetworkIndex, to_server: UnboundedSender<WorldToServer>) -> Vec<Hit> {
+    let tick_value = current_tick(world);
+
+    let mut hits = Vec::new();
+    let mut expired = Vec::new();
+    {
+        let mut grid_query = world.query::<&CollisionGrid>();
+        let collision_grid = grid_query.iter().next().map(|(_, grid)| grid);
+
+        let mut player_query = world.query::<(&Player, &PositionHistory, &PlayerCollision)>().without::<&Eliminated>();
+        let players: Vec<(Entity, &PositionHistory, &PlayerCollision)> = player_query.iter()
+            .map(|(entity, (_, history, collision))| (entity, history, collision))
+            .collect();
+
+        let mut projectile_query = world.query::<(&mut Projectile, &mut Position, &mut Velocity)>();
+        for (entity, (projectile, position, velocity)) in projectile_query.iter() {
+            if tick_value >= projectile.expires_tick {
+                expired.push(entity);
+                continue;
+            }
+
+            let shapes = match collision_grid {
+                Some(grid) => {
+                    let reach = projectile.radius + velocity.x.abs().max(velocity.y.abs());
+                    grid.query_circle(position.x, position.y, reach)
+                }
+                None => Vec::new(),
+            };
+            let wall = earliest_sweep(position.x, position.y, velocity.x, velocity.y, projectile.radius, &shapes);
+
+            // Targets are checked where they were `rewind_ticks` ago, as the thrower saw them
+            let rewound_tick = tick_value.saturating_sub(projectile.rewind_ticks);
+            let player = players.iter()
+                .filter(|(player, ..)| *player != projectile.owner)
+                .filter_map(|&(player, history, collision)| {
+                    let (x, y) = history.position_at(rewound_tick)?;
+                    let (x, y) = (x + collision.offset_x, y + collision.offset_y);
+                    let reach = projectile.radius + collision.radius;
+                    let (dx, dy) = (position.x - x, position.y - y);
+                    if dx * dx + dy * dy < reach * reach {
+                        return Some((0.0, player));
+                    }
+                    sweep_circle_point(position.x, position.y, velocity.x, velocity.y, reach, x, y)
+                        .map(|(t, _, _)| (t, player))
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+
+            match (player, wall) {
+                (Some((t, target)), wall) if wall.is_none_or(|(wall_t, _, _)| t <= wall_t) => {
+                    hits.push(Hit {
+                        target,
+                        attacker: projectile.owner,
+                        x: position.x + velocity.x * t,
+                        y: position.y + velocity.y * t,
+                    });
+                    expired.push(entity);
+                }
+                (_, Some((t, nx, ny))) => {
+                    if projectile.bounces_remaining == 0 {
+                        expired.push(entity);
+                        continue;
+                    }
+                    projectile.bounces_remaining -= 1;
+
+                    // Stop at the contact this tick and head off along the reflected direction next tick
+                    let length = (velocity.x * velocity.x + velocity.y * velocity.y).sqrt();
+                    let travel = (t - CONTACT_SKIN / length).max(0.0);
+                    position.x += velocity.x * travel;
+                    position.y += velocity.y * travel;
+                    let dot = velocity.x * nx + velocity.y * ny;
+                    velocity.x -= 2.0 * dot * nx;
+                    velocity.y -= 2.0 * dot * ny;
+                }
+                _ => {
+                    position.x += velocity.x;
+                    position.y += velocity.y;
+                }
+            }
+        }
+    }
+
+    for entity in expired {
+        despawn_networked(world, network_index, to_server.clone(), entity);
+    }
+
+    hits
+}
+
+pub fn broadcast_hits(world: &mut World, hits: &[Hit], to_server: UnboundedSender<WorldToServer>) {
+    for hit in hits {
+        let (Ok(target), Ok(attacker)) = (world.get::<&NetworkId>(hit.target), world.get::<&NetworkId>(hit.attacker)) else {
+            continue;
+        };
+        let (target, attacker) = (*target, *attacker);
+
+        for (_, connection) in world.query::<&Connection>().iter() {
+            to_server.send(WorldToServer::Hit {
+                receiver_connection_id: connection.connection_id,
+                target,
+                attacker,
+                x: hit.x,
+                y: hit.y,
+            }).unwrap();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Damage {
+    pub target: Entity,
+    pub attacker: Entity,
+    pub amount: u16,
+}
+
+pub fn damage_from_hits(world: &World, hits: &[Hit], config: &WorldConfig) -> Vec<Damage> {
+    hits.iter()
+        .filter(|hit| config.friendly_fire || !same_team(world, hit.target, hit.attacker))
+        .map(|hit| Damage { target: hit.target, attacker: hit.attacker, amount: config.projectile_damage })
+        .collect()
+}
+
+fn same_team(world: &World, a: Entity, b: Entity) -> bool {
+    match (world.get::<&Team>(a), world.get::<&Team>(b)) {
+        (Ok(a), Ok(b)) => *a == *b,
+        _ => false,
+    }
+}
+
+// Returns the damage that eliminated each newly eliminated player
+pub fn apply_damage(world: &mut World, damages: &[Damage], config: &WorldConfig, to_server: UnboundedSender<WorldToServer>) -> Vec<Damage> {
+    let tick_value = current_tick(world);
+
+    let mut eliminations = Vec::new();
+    for damage in damages {
+        let Ok((health, eliminated)) = world.query_one_mut::<(&mut Health, Option<&Eliminated>)>(damage.target) else {
+            continue;
+        };
+        if eliminated.is_some() || health.current == 0 {
+            continue;
+        }
+
+        health.current = health.current.saturating_sub(damage.amount);
+        if health.current == 0 {
+            eliminations.push(*damage);
+        }
+    }
+
+    for damage in &eliminations {
+        let _ = world.insert_one(damage.target, Eliminated { respawn_tick: tick_value + config.respawn_ticks });
+
+        let (Ok(target), Ok(attacker)) = (world.get::<&NetworkId>(damage.target), world.get::<&NetworkId>(damage.attacker)) else {
+            continue;
+        };
+        let (target, attacker) = (*target, *attacker);
+        for (_, connection) in world.query::<&Connection>().iter() {
+            to_server.send(WorldToServer::Eliminated {
+                receiver_connection_id: connection.connection_id,
+                target,
+                attacker,
+            }).unwrap();
+        }
+    }
+
+    eliminations
+}
+
+pub fn respawn_players(world: &mut World, config: &WorldConfig) {
+    let tick_value = current_tick(world);
+
+    let ready: Vec<Entity> = world.query::<&Eliminated>().iter()
+        .filter(|(_, eliminated)| tick_value >= eliminated.respawn_tick)
+        .map(|(entity, _)| entity)
+        .collect();
+
+    for entity in ready {
+        respawn_player(world, config, entity);
+    }
+}
+
+pub fn respawn_player(world: &mut World, config: &WorldConfig, entity: Entity) {
+    let _ = world.remove_one::<Eliminated>(entity);
+    let team = world.get::<&Team>(entity).ok().map(|team| team.id);
+    let (x, y) = choose_spawn_point(world, config.spawn_policy, team, PLAYER_RADIUS, Some(entity));
+
+    let Ok((position, velocity, target, path, health, history)) = world.query_one_mut::<(&mut Position, &mut Velocity, &mut MoveTarget, &mut Path, &mut Health, &mut PositionHistory)>(entity) else {
+        return;
+    };
+    position.x = x;
+    position.y = y;
+    velocity.x = 0.0;
+    velocity.y = 0.0;
+    target.x = x;
+    target.y = y;
+    path.waypoints.clear();
+    health.current = health.max;
+    // A rewind must never find the player back where they were eliminated
+    history.samples.clear();
+}
+
+pub fn update_interest(world: &mut World, grid: &mut SpatialGrid, registry: &ReplicationRegistry, config: &WorldConfig, to_server: UnboundedSender<WorldToServer>) {
+    let mut tick_value = 0;
+    for (_, tick) in world.query::<&Tick>().iter() {
+        tick_value = tick.tick;
+    }
+
+    grid.clear();
+    for (entity, (_, position)) in world.query::<(&NetworkId, &Position)>().iter() {
+        grid.insert(entity, position.x, position.y);
+    }
+
+    for (entity, (
+        connection,
+        network_id,
+        position,
+        interest,
+        replication,
+    )) in world.query::<(
+        &Connection,
+        &NetworkId,
+        &Position,
+        &mut Interest,
+        &mut Replication,
+    )>().iter() {
+        let mut in_view = HashMap::new();
+        in_view.insert(*network_id, entity);
+
+        // Entities already in view are kept until they pass the exit margin, so they don't flicker at the edge
+        let enter_radius_squared = interest.view_radius * interest.view_radius;
+        for other in grid.query_radius(position.x, position.y, interest.view_radius + config.view_exit_margin) {
+            let Ok(mut query) = world.query_one::<(&NetworkId, &Position)>(other) else {
+                continue;
+            };
+            let Some((other_network_id, other_position)) = query.get() else {
+                continue;
+            };
+
+            let dx = other_position.x - position.x;
+            let dy = other_position.y - position.y;
+            if dx * dx + dy * dy <= enter_radius_squared || interest.visible.contains(other_network_id) {
+                in_view.insert(*other_network_id, other);
+            }
+        }
+
+        for (&other_network_id, &other) in &in_view {
+            if interest.visible.contains(&other_network_id) {
+                continue;
+            }
+            let Ok(archetype) = world.get::<&Archetype>(other) else {
+                continue;
+            };
+
+            // The spawn carries a full snapshot, so later updates only need what changes after it
+            let (count, components) = match registry.diff(world, other, None, tick_value, config.state_refresh_ticks) {
+                Some(delta) => {
+                    replication.last_sent.insert(other_network_id, delta.sent);
+                    (delta.count, delta.bytes)
+                }
+                None => (0, Vec::new()),
+            };
+            replication.last_sent_tick.insert(other_network_id, tick_value);
+
+            to_server.send(WorldToServer::SpawnEntity {
+                receiver_connection_id: connection.connection_id,
+                network_id: other_network_id,
+                archetype: *archetype,
+                local: other == entity,
+                count,
+                components,
+            }).unwrap();
+        }
+
+        for &other_network_id in &interest.visible {
+            if !in_view.contains_key(&other_network_id) {
+                to_server.send(WorldToServer::DespawnEntity {
+                    receiver_connection_id: connection.connection_id,
+                    network_id: other_network_id,
+                }).unwrap();
+            }
+        }
+
+        interest.visible = in_view.into_keys().collect();
     }
 }
 
-pub fn broadcast_positions(world: &mut World, to_server: UnboundedSender<WorldToServer>) {
+pub fn broadcast_replication(world: &mut World, network_index: &NetworkIndex, registry: &ReplicationRegistry, config: &WorldConfig, to_server: UnboundedSender<WorldToServer>) {
+    let mut tick_value = 0;
+    for (_, tick) in world.query::<&Tick>().iter() {
+        tick_value = tick.tick;
+    }
+
     for (_,(
         broadcast_connection,
+        broadcast_network_id,
+        broadcast_position,
+        interest,
+        replication,
     )) in world.query::<(
         &Connection,
+        &NetworkId,
+        &Position,
+        &Interest,
+        &mut Replication,
     )>().iter() {
-        for (_,(
-            connection,
-            _,
-            position,
-        )) in world.query::<(
-            &Connection,
-            &Player,
-            &Position,
-        )>().iter() {
-            to_server.send(WorldToServer::UpdatePlayerPosition {
+        replication.last_sent_tick.retain(|network_id, _| interest.visible.contains(network_id));
+        replication.last_sent.retain(|network_id, _| interest.visible.contains(network_id));
+
+        let mut candidates = Vec::with_capacity(interest.visible.len());
+        for &network_id in &interest.visible {
+            let Some(entity) = network_index.get(network_id) else {
+                continue;
+            };
+            let Ok(position) = world.get::<&Position>(entity) else {
+                continue;
+            };
+
+            let dx = position.x - broadcast_position.x;
+            let dy = position.y - broadcast_position.y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            let last_sent_tick = replication.last_sent_tick.get(&network_id).copied().unwrap_or(0);
+            let relevance = if network_id == *broadcast_network_id { config.self_priority } else { 1.0 };
+            let priority = replication_priority(distance, interest.view_radius, tick_value.saturating_sub(last_sent_tick), relevance);
+
+            candidates.push((priority, network_id, entity));
+        }
+
+        // Highest priority first; whatever doesn't fit this tick waits and gains priority
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        let mut bytes_sent = 0;
+        for (_, network_id, entity) in candidates {
+            let Some(delta) = registry.diff(
+                world,
+                entity,
+                replication.last_sent.get(&network_id),
+                tick_value,
+                config.state_refresh_ticks,
+            ) else {
+                // Nothing changed, so the receiver is already up to date
+                replication.last_sent_tick.insert(network_id, tick_value);
+                continue;
+            };
+
+            let size = update_entity_size(delta.bytes.len());
+            if bytes_sent + size > replication.bytes_per_tick {
+                break;
+            }
+            bytes_sent += size;
+            replication.last_sent_tick.insert(network_id, tick_value);
+            replication.last_sent.insert(network_id, delta.sent);
+
+            to_server.send(WorldToServer::UpdateEntity {
                 receiver_connection_id: broadcast_connection.connection_id,
-                connection_id: connection.connection_id,
-                x: position.x,
-                y: position.y,
+                network_id,
+                count: delta.count,
+                components: delta.bytes,
             }).unwrap();
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,25 @@
+use hecs::World;
+
+use crate::components::*;
+use crate::config::WorldConfig;
+
+// Honours a valid requested team, otherwise joins the smallest team (lowest id on ties)
+pub fn assign_team(world: &World, config: &WorldConfig, requested: Option<u8>) -> Team {
+    if let Some(id) = requested
+        && id < config.team_count
+    {
+        return Team { id };
+    }
+
+    let mut sizes = vec![0usize; config.team_count as usize];
+    for (_, team) in world.query::<&Team>().iter() {
+        if let Some(size) = sizes.get_mut(team.id as usize) {
+            *size += 1;
+        }
+    }
+
+    let id = (0..config.team_count)
+        .min_by_key(|&id| sizes[id as usize])
+        .unwrap_or(0);
+    Team { id }
+}
@@ -0,0 +1,216 @@
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::map::*;
+
+// Tiled stores flip/rotation flags in the top bits of every gid
+const TILE_FLAG_MASK: u32 = 0xF000_0000;
+
+#[derive(Debug, Deserialize)]
+struct TiledMap {
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    orientation: String,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    layers: Vec<TiledLayer>,
+    #[serde(default)]
+    tilesets: Vec<TiledTileset>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TiledLayer {
+    Tilelayer {
+        width: u32,
+        height: u32,
+        #[serde(default)]
+        data: Vec<u32>,
+        #[serde(default = "default_visible")]
+        visible: bool,
+    },
+    Objectgroup {
+        #[serde(default)]
+        objects: Vec<TiledObject>,
+    },
+    Group {
+        #[serde(default)]
+        layers: Vec<TiledLayer>,
+    },
+    Imagelayer {},
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledObject {
+    #[serde(default)]
+    name: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default)]
+    point: bool,
+    #[serde(default)]
+    ellipse: bool,
+    polyline: Option<Vec<TiledPoint>>,
+    polygon: Option<Vec<TiledPoint>>,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct TiledPoint {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledTileset {
+    firstgid: u32,
+    image: Option<String>,
+    source: Option<String>,
+    #[serde(default)]
+    columns: u32,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+pub fn import_tiled_map(data: &str) -> Result<Map> {
+    let tiled: TiledMap = serde_json::from_str(data)?;
+
+    if !tiled.orientation.is_empty() && tiled.orientation != "orthogonal" {
+        bail!("unsupported Tiled orientation '{}'", tiled.orientation);
+    }
+    // Infinite maps keep their tiles in chunks rather than `data`
+    if tiled.infinite {
+        bail!("infinite Tiled maps are not supported, turn off 'Infinite' in the map properties");
+    }
+
+    let (Some(width), Some(height)) = (tiled.width.checked_mul(tiled.tilewidth), tiled.height.checked_mul(tiled.tileheight)) else {
+        bail!("map of {}x{} tiles at {}x{} is too large", tiled.width, tiled.height, tiled.tilewidth, tiled.tileheight);
+    };
+
+    let mut map = Map {
+        width: width as f32,
+        height: height as f32,
+        collision: Vec::new(),
+        circles: Vec::new(),
+        spawn_points: Vec::new(),
+        regions: Vec::new(),
+        tilesets: Vec::new(),
+        tile_layers: Vec::new(),
+    };
+
+    for tileset in &tiled.tilesets {
+        if let Some(source) = &tileset.source {
+            bail!("external tileset '{}' is not supported, embed it in the map", source);
+        }
+        let Some(image) = &tileset.image else {
+            bail!("tileset at gid {} has no image, image collection tilesets are not supported", tileset.firstgid);
+        };
+
+        map.tilesets.push(MapTileset {
+            first_gid: tileset.firstgid,
+            image: image.clone(),
+            columns: tileset.columns,
+            tile_width: tileset.tilewidth,
+            tile_height: tileset.tileheight,
+        });
+    }
+
+    import_layers(&tiled.layers, &tiled, &mut map);
+
+    Ok(map)
+}
+
+fn import_layers(layers: &[TiledLayer], tiled: &TiledMap, map: &mut Map) {
+    for layer in layers {
+        match layer {
+            TiledLayer::Tilelayer { width, height, data, visible } => {
+                if !visible || data.is_empty() {
+                    continue;
+                }
+                map.tile_layers.push(MapTileLayer {
+                    width: *width,
+                    height: *height,
+                    tile_width: tiled.tilewidth,
+                    tile_height: tiled.tileheight,
+                    data: data.iter().map(|gid| gid & !TILE_FLAG_MASK).collect(),
+                });
+            }
+            TiledLayer::Objectgroup { objects } => {
+                for object in objects {
+                    import_object(object, map);
+                }
+            }
+            TiledLayer::Group { layers } => {
+                import_layers(layers, tiled, map);
+            }
+            TiledLayer::Imagelayer {} => {}
+        }
+    }
+}
+
+fn import_object(object: &TiledObject, map: &mut Map) {
+    // Object points are relative to the object origin, which the rotation pivots around
+    let (sin, cos) = object.rotation.to_radians().sin_cos();
+    let transform = |point: &TiledPoint| MapPoint {
+        x: object.x + point.x * cos - point.y * sin,
+        y: object.y + point.x * sin + point.y * cos,
+    };
+
+    if let Some(points) = &object.polyline {
+        map.collision.push(MapPolyline {
+            points: points.iter().map(transform).collect(),
+            closed: false,
+        });
+    } else if let Some(points) = &object.polygon {
+        map.collision.push(MapPolyline {
+            points: points.iter().map(transform).collect(),
+            closed: true,
+        });
+    } else if object.ellipse {
+        // Circles only; an ellipse collides as the circle through its average radius
+        let radius = (object.width + object.height) / 4.0;
+        let center = transform(&TiledPoint { x: object.width / 2.0, y: object.height / 2.0 });
+        map.circles.push(MapCircle { x: center.x, y: center.y, radius });
+    } else if object.point {
+        map.spawn_points.push(MapSpawnPoint { x: object.x, y: object.y, team: team_property(object) });
+    } else if !object.name.is_empty() {
+        map.regions.push(MapRegion {
+            name: object.name.clone(),
+            x: object.x,
+            y: object.y,
+            width: object.width,
+            height: object.height,
+            team: team_property(object),
+        });
+    }
+}
+
+fn team_property(object: &TiledObject) -> Option<u8> {
+    object.properties.iter()
+        .find(|property| property.name == "team")
+        .and_then(|property| property.value.as_u64())
+        .and_then(|team| u8::try_from(team).ok())
+}
@@ -1,55 +1,107 @@
+use tokio::sync::mpsc::error::TryRecvError;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use crate::messages::{ServerToWorld, WorldToServer};
 use tokio::time::{interval, Duration};
 use anyhow::Result;
+use std::sync::Arc;
 
 
 use hecs::World;
 use crate::components::*;
+use crate::config::WorldConfig;
+use crate::game_mode::*;
+use crate::index::{ConnectionIndex, NetworkIndex};
+use crate::interest::SpatialGrid;
+use crate::map::{spawn_map, Map};
+use crate::replication::default_registry;
 use crate::systems::*;
 
 pub async fn run_world(
     mut from_server: UnboundedReceiver<ServerToWorld>,
     to_server: UnboundedSender<WorldToServer>,
+    config: WorldConfig,
+    map: Arc<Map>,
 ) -> Result<()> {
-    let mut tick = interval(Duration::from_secs_f64(1.0 / 30.0));
+    let mut tick = interval(Duration::from_secs_f64(1.0 / f64::from(config.tick_rate)));
 
     //Initialise World
     let mut world = World::new();
+    let mut connection_index = ConnectionIndex::new();
+    let mut network_index = NetworkIndex::new();
+    let mut interest_grid = SpatialGrid::new(config.interest_cell_size);
+    let mut separation_grid = SpatialGrid::new(PLAYER_RADIUS * 4.0);
+    let replication_registry = default_registry();
     world.spawn((Tick { tick: 0 },));
 
-    world.spawn((
-        Collision {
-            collision_lines: vec![
-                CollisionLine { x1: 192.0, y1: 128.0, x2: 320.0, y2: 128.0 },
-                CollisionLine { x1: 320.0, y1: 128.0, x2: 320.0, y2: 256.0 },
-                CollisionLine { x1: 320.0, y1: 256.0, x2: 296.0, y2: 208.0 },
-                CollisionLine { x1: 296.0, y1: 208.0, x2: 248.0, y2: 256.0 },
-            ]
-        },
-    ));
-    
+    spawn_map(&mut world, &map);
+    spawn_match(&mut world, &config);
+
     loop {
         tick.tick().await;
 
         update_tick(&mut world, to_server.clone());
 
-        // Process messages from the world
-        while let Ok(msg) = from_server.try_recv() {
+        // Process messages from the server; the room closing drops the sender and ends the loop
+        loop {
+            let msg = match from_server.try_recv() {
+                Ok(msg) => msg,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            };
             match msg {
-                ServerToWorld::PlayerJoined { connection_id } => {
-
-                    create_player(&mut world, to_server.clone(), connection_id, 256.0, 192.0);
+                ServerToWorld::PlayerJoined { connection_id, team } => {
+                    to_server.send(WorldToServer::SendMap { receiver_connection_id: connection_id, map: map.clone() })?;
+                    create_player(&mut world, &mut connection_index, &mut network_index, &config, connection_id, team);
+                    if let Some(entity) = connection_index.get(connection_id) {
+                        admit_player(&mut world, entity);
+                    }
+                    send_match(&world, connection_id, to_server.clone());
+                }
+                ServerToWorld::PlayerLeft { connection_id } => {
+                    despawn_player(&mut world, &mut connection_index, &mut network_index, to_server.clone(), connection_id);
                 }
                 ServerToWorld::InputClickPressed { connection_id, x, y } => {
-                    input_click_pressed(&mut world, connection_id, x, y);
+                    input_click_pressed(&mut world, &connection_index, connection_id, x, y);
+                }
+                ServerToWorld::InputClickHold { connection_id, x, y } => {
+                    input_click_hold(&mut world, &connection_index, connection_id, x, y);
+                }
+                ServerToWorld::InputClickReleased { connection_id } => {
+                    input_click_released(&mut world, &connection_index, connection_id);
+                }
+                ServerToWorld::InputCommand { connection_id, command } => {
+                    input_command(&mut world, &connection_index, connection_id, command);
                 }
             }
         }
 
-        update_state(&mut world);
-        handle_state(&mut world);
-        apply_velocity(&mut world);
-        broadcast_positions(&mut world, to_server.clone());
+        update_match(&mut world, &mut network_index, &config, to_server.clone());
+        let phase = current_phase(&world);
+
+        expire_held_input(&mut world);
+        if phase.allows_respawn() {
+            respawn_players(&mut world, &config);
+        }
+        if phase.allows_movement() {
+            follow_path(&mut world);
+            update_state(&mut world);
+            handle_state(&mut world);
+            resolve_player_collisions(&mut world, &mut separation_grid);
+            apply_velocity(&mut world);
+        }
+        record_position_history(&mut world, &config);
+        if phase.allows_movement() {
+            throw_projectiles(&mut world, &mut network_index, &config);
+        }
+        let hits = update_projectiles(&mut world, &mut network_index, to_server.clone());
+        broadcast_hits(&mut world, &hits, to_server.clone());
+        if phase.allows_damage() {
+            let damages = damage_from_hits(&world, &hits, &config);
+            let eliminations = apply_damage(&mut world, &damages, &config, to_server.clone());
+            score_eliminations(&mut world, &eliminations);
+        }
+        broadcast_match(&mut world, to_server.clone());
+        update_interest(&mut world, &mut interest_grid, &replication_registry, &config, to_server.clone());
+        broadcast_replication(&mut world, &network_index, &replication_registry, &config, to_server.clone());
     }
 }
\ No newline at end of file